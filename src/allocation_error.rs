@@ -5,48 +5,229 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::convert::Infallible;
 use std::fmt;
 use std::error::Error;
 
+/// The memory storages of the allocators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageKind {
+    /// The memory storage holding data implementing the `Drop` trait.
+    Drop,
+    /// The memory storage holding data implementing the `Copy` trait.
+    Copy,
+}
+
+impl fmt::Display for StorageKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StorageKind::Drop => write!(f, "droppable data storage"),
+            StorageKind::Copy => write!(f, "copyable data storage"),
+        }
+    }
+}
+
 /// A custom error enumeration, used by AllocationResult as the error type.
-/// Handle "out of memory" errors.
-#[derive(Debug)]
+///
+/// The errors only hold plain data describing what went wrong. Creating and displaying them never allocates memory,
+/// they can be used safely when the memory is scarce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllocationError {
-    OutOfMemoryError(String),
-    OutOfPoolError(String),
+    /// The memory storage doesn't have enough remaining memory to store the requested data.
+    OutOfMemoryError {
+        /// The size of the data, in bytes.
+        requested_size: usize,
+        /// The alignment of the data, in bytes.
+        alignment: usize,
+        /// The memory storage in which the data should have been placed.
+        storage: StorageKind,
+        /// The index of the first unused byte of the memory storage when the allocation was requested.
+        fill: usize,
+        /// The capacity of the memory storage, in bytes.
+        capacity: usize,
+    },
+    /// All the blocks of a pool are in use.
+    OutOfPoolError {
+        /// The size of a block of the pool, in bytes.
+        block_size: usize,
+        /// The number of blocks of the pool.
+        block_count: usize,
+    },
+    /// The requested data doesn't fit in a block of the pool.
+    BlockSizeError {
+        /// The size of the data, in bytes.
+        requested_size: usize,
+        /// The alignment of the data, in bytes.
+        alignment: usize,
+        /// The size of a block of the pool, in bytes.
+        block_size: usize,
+        /// The alignment of the blocks of the pool, in bytes.
+        block_alignment: usize,
+    },
+    /// The marker doesn't designate a valid location in the memory storage.
+    InvalidMarkerError {
+        /// The given marker.
+        marker: usize,
+        /// The index of the first unused byte of the memory storage.
+        fill: usize,
+        /// The memory storage the marker was given to.
+        storage: StorageKind,
+    },
+    /// The marker lies in the region of a child allocator which has not been dropped yet.
+    ChildInUseError {
+        /// The given marker.
+        marker: usize,
+        /// The index of the first byte after the region of the child allocator, in the memory storage holding
+        /// data implementing the `Copy` trait.
+        child_end: usize,
+    },
+    /// The memory storage is dropping its objects, it cannot be used until the reset is complete.
+    ResetInProgressError {
+        /// The memory storage being reset.
+        storage: StorageKind,
+    },
+    /// Computing the location of the requested data overflowed the address space.
+    CapacityOverflowError {
+        /// The size of the data, in bytes.
+        requested_size: usize,
+        /// The alignment of the data, in bytes.
+        alignment: usize,
+        /// The memory storage in which the data should have been placed.
+        storage: StorageKind,
+    },
 }
 
-unsafe impl Send for AllocationError {}
-unsafe impl Sync for AllocationError {}
-
 impl fmt::Display for AllocationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &AllocationError::OutOfMemoryError(ref description) => {
-                write!(f, "Out of memory error: {}", description)
-            }
-            &AllocationError::OutOfPoolError(ref description) => {
-                write!(f, "Out of pool error: {}", description)
-            }
+        match *self {
+            AllocationError::OutOfMemoryError {
+                requested_size,
+                alignment,
+                storage,
+                fill,
+                capacity,
+            } => write!(
+                f,
+                "Out of memory error: cannot allocate {} bytes aligned on {} bytes in the {} ({} / {} bytes used)",
+                requested_size, alignment, storage, fill, capacity
+            ),
+            AllocationError::OutOfPoolError {
+                block_size,
+                block_count,
+            } => write!(
+                f,
+                "Out of pool error: all the {} blocks of {} bytes are in use",
+                block_count, block_size
+            ),
+            AllocationError::BlockSizeError {
+                requested_size,
+                alignment,
+                block_size,
+                block_alignment,
+            } => write!(
+                f,
+                "Block size error: {} bytes aligned on {} bytes don't fit in a block of {} bytes aligned on {} bytes",
+                requested_size, alignment, block_size, block_alignment
+            ),
+            AllocationError::InvalidMarkerError {
+                marker,
+                fill,
+                storage,
+            } => write!(
+                f,
+                "Invalid marker error: the marker {} is not a valid location in the {} ({} bytes used)",
+                marker, storage, fill
+            ),
+            AllocationError::ChildInUseError { marker, child_end } => write!(
+                f,
+                "Child in use error: the marker {} lies below the end of a living child allocator, at byte {}",
+                marker, child_end
+            ),
+            AllocationError::ResetInProgressError { storage } => write!(
+                f,
+                "Reset in progress error: the {} cannot be used while its objects are being dropped",
+                storage
+            ),
+            AllocationError::CapacityOverflowError {
+                requested_size,
+                alignment,
+                storage,
+            } => write!(
+                f,
+                "Capacity overflow error: the location of {} bytes aligned on {} bytes in the {} overflows",
+                requested_size, alignment, storage
+            ),
         }
     }
 }
 
 impl Error for AllocationError {
     fn description(&self) -> &str {
-        match self {
-            &AllocationError::OutOfMemoryError(_) => "OutOfMemoryError",
-            &AllocationError::OutOfPoolError(_) => "OutOfPoolError",
+        match *self {
+            AllocationError::OutOfMemoryError { .. } => "OutOfMemoryError",
+            AllocationError::OutOfPoolError { .. } => "OutOfPoolError",
+            AllocationError::BlockSizeError { .. } => "BlockSizeError",
+            AllocationError::InvalidMarkerError { .. } => "InvalidMarkerError",
+            AllocationError::ChildInUseError { .. } => "ChildInUseError",
+            AllocationError::ResetInProgressError { .. } => "ResetInProgressError",
+            AllocationError::CapacityOverflowError { .. } => "CapacityOverflowError",
         }
     }
 
-    fn cause(&self) -> Option<&Error> {
-        match self {
-            &AllocationError::OutOfMemoryError(_) => None,
-            &AllocationError::OutOfPoolError(_) => None,
-        }
+    fn source(&self) -> Option<&(Error + 'static)> {
+        None
     }
 }
 
 /// A simple typedef, for convenience.
 pub type AllocationResult<T> = Result<T, AllocationError>;
+
+/// The error returned when allocating data with a fallible initializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryAllocError<E> {
+    /// The data could not be allocated.
+    AllocationError(AllocationError),
+    /// The initializer of the data returned an error.
+    InitializerError(E),
+}
+
+impl TryAllocError<Infallible> {
+    /// Extracts the allocation error, an infallible initializer cannot return an error.
+    pub(crate) fn into_allocation_error(self) -> AllocationError {
+        match self {
+            TryAllocError::AllocationError(error) => error,
+            TryAllocError::InitializerError(never) => match never {},
+        }
+    }
+}
+
+impl<E> From<AllocationError> for TryAllocError<E> {
+    fn from(error: AllocationError) -> Self {
+        TryAllocError::AllocationError(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for TryAllocError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TryAllocError::AllocationError(ref error) => write!(f, "{}", error),
+            TryAllocError::InitializerError(ref error) => write!(f, "Initializer error: {}", error),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for TryAllocError<E> {
+    fn description(&self) -> &str {
+        match *self {
+            TryAllocError::AllocationError(ref error) => error.description(),
+            TryAllocError::InitializerError(_) => "InitializerError",
+        }
+    }
+
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            TryAllocError::AllocationError(ref error) => Some(error),
+            TryAllocError::InitializerError(ref error) => Some(error),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::Location;
+
+/// A single allocation, recorded with the source location which asked for it.
+#[derive(Debug, Clone, Copy)]
+pub struct AllocationRecord {
+    location: &'static Location<'static>,
+    /// Index of the first byte used by the allocation (header and padding included).
+    start: usize,
+    /// Index of the first byte after the allocation.
+    end: usize,
+}
+
+/// Records the call site of every allocation made in a memory storage.
+///
+/// The records are kept in allocation order, so they can be truncated when the memory storage
+/// is reset to a marker.
+#[derive(Debug, Default)]
+pub struct CallSiteTracker {
+    records: RefCell<Vec<AllocationRecord>>,
+}
+
+impl CallSiteTracker {
+    /// Creates an empty tracker.
+    pub fn new() -> Self {
+        debug!("Creating a new call site tracker.");
+        CallSiteTracker {
+            records: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Records an allocation, lying between the bytes `start` and `end`, asked by `location`.
+    pub fn record(&self, location: &'static Location<'static>, start: usize, end: usize) {
+        trace!("Recording an allocation from byte {} to byte {}, asked at {}.", start, end, location);
+        self.records.borrow_mut().push(AllocationRecord {
+            location,
+            start,
+            end,
+        });
+    }
+
+    /// Forgets all the allocations lying after the given marker.
+    pub fn truncate(&self, marker: usize) {
+        trace!("Forgetting the allocations recorded after the byte {}.", marker);
+        self.records.borrow_mut().retain(|record| record.start < marker);
+    }
+
+    /// Forgets every recorded allocation.
+    pub fn clear(&self) {
+        trace!("Forgetting all the recorded allocations.");
+        self.records.borrow_mut().clear();
+    }
+}
+
+/// The number of allocations and bytes used by a given source location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallSiteStatistics {
+    /// The source location which asked for the allocations.
+    pub location: &'static Location<'static>,
+    /// The number of live allocations asked by this source location.
+    pub count: usize,
+    /// The number of bytes used by those allocations, headers and padding included.
+    pub bytes: usize,
+}
+
+/// A report of the live allocations of an allocator, aggregated by source location.
+///
+/// The call sites are sorted by the number of bytes they use, the most memory-hungry first.
+///
+/// This structure is only available with the `allocation_tracking` feature.
+#[derive(Debug, Clone, Default)]
+pub struct AllocationReport {
+    call_sites: Vec<CallSiteStatistics>,
+}
+
+impl AllocationReport {
+    /// Creates a report from the allocations recorded by the given trackers.
+    pub(crate) fn from_trackers(trackers: &[&CallSiteTracker]) -> Self {
+        debug!("Creating an allocation report from {} call site trackers.", trackers.len());
+        let mut report = AllocationReport {
+            call_sites: Vec::new(),
+        };
+
+        for tracker in trackers.iter() {
+            for record in tracker.records.borrow().iter() {
+                report.add(record.location, record.end - record.start);
+            }
+        }
+
+        //The most memory-hungry call sites first.
+        report
+            .call_sites
+            .sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.count.cmp(&a.count)));
+        report
+    }
+
+    fn add(&mut self, location: &'static Location<'static>, bytes: usize) {
+        if let Some(call_site) = self.call_sites
+            .iter_mut()
+            .find(|call_site| call_site.location == location)
+        {
+            call_site.count += 1;
+            call_site.bytes += bytes;
+            return;
+        }
+
+        self.call_sites.push(CallSiteStatistics {
+            location,
+            count: 1,
+            bytes,
+        });
+    }
+
+    /// Returns the statistics of every call site having live allocations.
+    pub fn call_sites(&self) -> &[CallSiteStatistics] {
+        &self.call_sites
+    }
+
+    /// Returns the total number of bytes used by the live allocations.
+    pub fn total_bytes(&self) -> usize {
+        self.call_sites.iter().map(|call_site| call_site.bytes).sum()
+    }
+
+    /// Returns the total number of live allocations.
+    pub fn total_count(&self) -> usize {
+        self.call_sites.iter().map(|call_site| call_site.count).sum()
+    }
+}
+
+impl fmt::Display for AllocationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>10} {:>8}  call site", "bytes", "count")?;
+        for call_site in self.call_sites.iter() {
+            writeln!(
+                f,
+                "{:>10} {:>8}  {}",
+                call_site.bytes, call_site.count, call_site.location
+            )?;
+        }
+        write!(f, "{:>10} {:>8}  total", self.total_bytes(), self.total_count())
+    }
+}
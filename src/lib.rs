@@ -58,7 +58,7 @@
 //! A `DoubleBufferedAllocator` can be used as a "two-frames" buffer.
 //!
 //! ```rust
-//! use maskerad_memory_allocators::DoubleBufferedAllocator;
+//! use maskerad_memory_allocators::{DoubleBufferedAllocator, FrameRef};
 //! # use std::error::Error;
 //! # fn try_main() -> Result<(), Box<Error>> {
 //! //100 bytes for data implementing the Drop trait, 100 bytes for data implementing the `Copy` trait.
@@ -66,15 +66,12 @@
 //! let mut closed = false;
 //!
 //! while !closed {
-//!     //swap the active and inactive buffers of the allocator.
-//!     allocator.swap_buffers();
-//!
-//!     //clear the newly active buffer.
-//!     allocator.reset();
+//!     //swap the active and inactive buffers of the allocator, and clear the newly active buffer.
+//!     allocator.begin_frame();
 //!
 //!     //allocate with the current buffer, leaving the data in the inactive buffer intact.
 //!     //You can use this data during this frame, or the next frame.
-//!     let my_vec: &Vec<u8> = allocator.alloc(|| {
+//!     let my_vec: FrameRef<Vec<u8>> = allocator.alloc(|| {
 //!         Vec::with_capacity(10)
 //!     })?;
 //!
@@ -109,8 +106,25 @@ mod smart_pointers;
 mod pools;
 
 mod memory_chunk;
+mod memory_dump;
 pub mod allocation_error;
 mod utils;
+#[cfg(feature = "allocation_tracking")]
+mod allocation_tracking;
 
-pub use stacks::stack_allocator::StackAllocator;
-pub use stacks::double_buffered_allocator::DoubleBufferedAllocator;
+pub use stacks::stack_allocator::{AllocatorStatistics, ChildAllocator, InPlace, Initialized, StackAllocator, StackHandle, SubAllocator};
+pub use stacks::frozen_stack::FrozenStack;
+pub use stacks::double_buffered_allocator::{BufferMarker, DoubleBufferedAllocator, FrameRef};
+pub use stacks::multi_buffered_allocator::MultiBufferedAllocator;
+pub use stacks::frame_allocator::{FrameAllocator, FrameScope, FrameToken};
+pub use stacks::shared_double_buffer::{double_buffered_channel, DoubleBufferReader, DoubleBufferWriter, ReadBuffer};
+pub use stacks::sync_stack_allocator::SyncStackAllocator;
+pub use stacks::allocator_set::{AllocatorSet, LocalAllocator};
+pub use stacks::scratch::{with_scratch, with_scratch_avoiding, SCRATCH_CAPACITY};
+pub use pools::pool_allocator::PoolAllocator;
+pub use smart_pointers::unique_ptr::UniquePtr;
+pub use memory_chunk::{AllocationInfo, Allocations};
+pub use memory_dump::DumpFormat;
+pub use allocation_error::{AllocationError, AllocationResult, StorageKind, TryAllocError};
+#[cfg(feature = "allocation_tracking")]
+pub use allocation_tracking::{AllocationReport, CallSiteStatistics};
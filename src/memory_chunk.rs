@@ -6,8 +6,10 @@
 // copied, modified, or distributed except according to those terms.
 
 use alloc::raw_vec::RawVec;
-use std::cell::Cell;
+use std::cell::{Cell, Ref};
 use core::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
 
 use std::fmt;
 
@@ -23,31 +25,130 @@ use utils;
 /// when objects in the memory chunk are dropped.
 ///
 /// - The chunk extracts, for the types implementing the Drop trait,
-/// some info about the type (its virtual table) and place it next to the object. The chunk is able to call the drop method of the object
-/// with the virtual table.
+/// some info about the type (its virtual table and, in debug builds, its name) and place it in a header next to the object.
+/// The chunk is able to call the drop method of the object with the virtual table.
+///
+/// - A footer after every object implementing the Drop trait holds the index of its header. The chunk walks
+/// its objects backward, dropping them in the reverse order of their allocation.
 ///
 ///
 /// You should not use the MemoryChunk directly. The allocators manage memory chunks, use them.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MemoryChunk {
+    /// The buffer of the chunk, freed when the chunk is dropped. Empty if the chunk uses memory owned by another chunk.
+    #[allow(dead_code)]
     storage: RawVec<u8>,
+    /// Pointer to the first byte of the memory storage: the buffer of the RawVec, or memory owned by another chunk.
+    ptr: *mut u8,
+    /// Size of the memory storage, in bytes.
+    capacity: usize,
     /// Index of the first unused byte.
     fill: Cell<usize>,
+    /// Whether or not the chunk is dropping its objects.
+    destroying: Cell<bool>,
+}
+
+/// Informations about an object living in a memory storage holding data implementing the `Drop` trait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationInfo {
+    /// Index of the header of the object.
+    pub header: usize,
+    /// Index of the object.
+    pub offset: usize,
+    /// Size of the object, in bytes.
+    pub size: usize,
+    /// Alignment of the object, in bytes.
+    pub alignment: usize,
+    /// Index of the footer of the object, holding the index of its header.
+    pub footer: usize,
+    /// Index of the first byte after the allocation (footer included), where the next header would be written.
+    pub end: usize,
+    /// Whether or not the object has been initialized, and will be dropped when the memory storage is reset.
+    pub is_initialized: bool,
+    /// The name of the type of the object. Only available in debug builds.
+    pub type_name: Option<&'static str>,
+}
+
+/// An iterator over the objects living in a memory storage holding data implementing the `Drop` trait,
+/// from the bottom of the stack to its top.
+///
+/// This structure is created by the `allocations()` function of the allocators.
+pub struct Allocations<'a> {
+    chunk: Ref<'a, MemoryChunk>,
+    index: usize,
+    fill: usize,
+}
+
+impl<'a> Allocations<'a> {
+    /// Creates an iterator over the objects living in the given memory chunk.
+    pub(crate) fn new(chunk: Ref<'a, MemoryChunk>) -> Self {
+        debug!("Creating an iterator over the data contained in a memory chunk.");
+        let fill = chunk.fill();
+        Allocations {
+            chunk,
+            index: 0,
+            fill,
+        }
+    }
+}
+
+impl<'a> Iterator for Allocations<'a> {
+    type Item = AllocationInfo;
+
+    fn next(&mut self) -> Option<AllocationInfo> {
+        if self.index >= self.fill {
+            return None;
+        }
+
+        //The allocators only write headers between the bottom of the stack and the first unused byte.
+        let info = unsafe { self.chunk.allocation_info(self.index) };
+        self.index = info.end;
+        Some(info)
+    }
+}
+
+impl<'a> fmt::Debug for Allocations<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Allocations [Next header: byte {}, First unused byte: {}]", self.index, self.fill)
+    }
 }
 
 impl fmt::Debug for MemoryChunk {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "MemoryChunk [Max capacity: {} bytes, Memory used: {} bytes]", self.storage.cap(), self.fill.get())
+        write!(f, "MemoryChunk [Max capacity: {} bytes, Memory used: {} bytes]", self.capacity, self.fill.get())
     }
 }
 
+//The raw pointer points to the buffer of the chunk, or to memory lent to the chunk for its whole life.
+unsafe impl Send for MemoryChunk {}
+
 impl MemoryChunk {
     /// Creates a new memory chunk, allocating the given number of bytes.
     pub fn new(size: usize) -> Self {
         debug!("Creating a new memory chunk of {} bytes in size.", size);
+        let storage = RawVec::with_capacity(size);
+        MemoryChunk {
+            ptr: storage.ptr(),
+            capacity: storage.cap(),
+            storage,
+            fill: Cell::new(0),
+            destroying: Cell::new(false),
+        }
+    }
+
+    /// Creates a new memory chunk over the given bytes, owned by something else.
+    ///
+    /// The memory is not freed when the chunk is dropped. It must stay valid, and must not be used by anything else,
+    /// until the chunk is dropped.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, size: usize) -> Self {
+        debug!("Creating a new memory chunk of {} bytes in size, over borrowed memory.", size);
         MemoryChunk {
-            storage: RawVec::with_capacity(size),
+            //An empty RawVec does not allocate.
+            storage: RawVec::with_capacity(0),
+            ptr,
+            capacity: size,
             fill: Cell::new(0),
+            destroying: Cell::new(false),
         }
     }
 
@@ -63,83 +164,166 @@ impl MemoryChunk {
         self.fill.set(first_unused_byte)
     }
 
+    /// Returns whether or not the chunk is dropping its objects.
+    ///
+    /// The destructors of the objects must not allocate in the chunk or reset it while they run.
+    pub fn is_destroying(&self) -> bool {
+        self.destroying.get()
+    }
+
     /// Returns the maximal number of bytes the chunk can store.
     pub fn capacity(&self) -> usize {
         debug!("Getting the maximum capacity in byte of the memory chunk.");
-        self.storage.cap()
+        self.capacity
     }
 
     /// Returns a pointer to the start of the memory storage used by the chunk.
     pub fn as_ptr(&self) -> *const u8 {
         debug!("Getting a raw pointer to the start of the allocation of the memory chunk.");
-        self.storage.ptr()
+        self.ptr
     }
 
     /// Drop all the data contained in the chunk.
-    pub unsafe fn destroy(&self) {
+    ///
+    /// See `destroy_to_marker()` for the behavior when a destructor panics.
+    pub unsafe fn destroy(&self) -> thread::Result<()> {
         debug!("Dropping all the data contained in the memory chunk.");
-        self.destroy_to_marker(0);
+        self.destroy_to_marker(0)
     }
 
-    /// Drop the data contained in the chunk, starting from the given marker.
-    pub unsafe fn destroy_to_marker(&self, marker: usize) {
+    /// Drop the data contained in the chunk, down to the given marker.
+    ///
+    /// The objects are dropped in the reverse order of their allocation: the last allocated object is dropped first.
+    ///
+    /// If a destructor panics, the remaining objects are still dropped. The payload of the first panic is returned,
+    /// the caller should resume it once the chunk is in a consistent state. The payloads of the other panics are discarded.
+    pub unsafe fn destroy_to_marker(&self, marker: usize) -> thread::Result<()> {
         debug!("Dropping the data lying between the byte {} and the byte {}, contained in the memory chunk.", marker, self.fill.get());
-        //Get the index of the marker.
-        //We'll start dropping the content from this location.
-        let mut index = marker;
+        //Get the index of the first unused memory address.
+        //We'll start dropping the content from this location, the top of the stack.
+        let mut index = self.fill.get();
 
         //Get a raw pointer to the bottom of the memory storage.
         let storage_start = self.as_ptr();
 
-        //Get the index of the first unused memory address.
-        //We'll stop dropping the content when we are at this location.
-        let fill = self.fill.get();
-
-        //While the starting index is inferior to the ending one...
-        trace!("Iterating over all the data contained in the memory chunk...");
-        while index < fill {
-            //Get a raw pointer on the TypeDescription of the object.
-            trace!("Getting a raw pointer to the memory location of the TypeDescription of the next data.");
-            let type_description_data = storage_start.offset(index as isize) as *const usize;
-
-            //Decode this raw pointer to obtain the vtable of the object, and a boolean to know if
-            //the object has been initialized.
-            trace!("Unpacking the memory location to get the TypeDescription and its 'is_done' state.");
-            let (type_description, is_done) =
-                utils::un_bitpack_type_description_ptr(*type_description_data);
-
-            //Get the size and the alignment of the object, with its type description.
-            trace!("Getting the size and the alignment of the data.");
-            let (size, alignment) = ((*type_description).size, (*type_description).alignment);
-            trace!("size: {}, align: {}", size, alignment);
-
-            //Get the index of the memory address just after the type description.
-            //It's the unaligned memory address of the object.
-            trace!("Getting the index of the memory location just after this TypeDescription (unaligned memory location of the data).");
-            let after_type_description = index + mem::size_of::<*const utils::TypeDescription>();
-            trace!("unaligned memory location: {:x}", after_type_description);
-
-            //Get the aligned memory address, with the unaligned one and the alignment of the object.
-            //This is where the object *really* lives.
-            trace!("Getting the aligned memory location of the data.");
-            let start = utils::round_up(after_type_description, alignment);
-            trace!("aligned memory location: {:x}", start);
+        //The payload of the first panicking destructor, if any.
+        let mut result = Ok(());
+
+        //Forbid the destructors to allocate in the chunk or to reset it, they would corrupt the walk.
+        self.destroying.set(true);
+
+        //While the index is above the marker...
+        trace!("Iterating over all the data contained in the memory chunk, from the top of the stack...");
+        while index > marker {
+            //Read the footer of the last object, just before the index, to find its header.
+            trace!("Reading the footer of the previous data.");
+            let footer = storage_start.offset((index - mem::size_of::<utils::AllocationFooter>()) as isize)
+                as *const utils::AllocationFooter;
+            index = (*footer).header;
+
+            //Read the header of the object, to know where the object lives and if it has been initialized.
+            trace!("Reading the header of the previous data.");
+            let info = self.allocation_info(index);
 
             //If the object has been successfully initialized, we can call its drop function.
             //We call the function pointer of the object's vtable, here drop_glue, and give him the pointer
             //to the location of the object.
-            if is_done {
+            if info.is_initialized {
                 trace!("The data had been successfully initialized when allocated. Dropping the data.");
-                ((*type_description).drop_glue)(storage_start.offset(start as isize) as *const i8);
+                let header = storage_start.offset(index as isize) as *const utils::AllocationHeader;
+                let (type_description, _) = utils::un_bitpack_type_description_ptr((*header).type_description);
+                let object = storage_start.offset(info.offset as isize) as *const i8;
+
+                //A panicking destructor must not prevent the other objects from being dropped.
+                let dropped = panic::catch_unwind(AssertUnwindSafe(|| ((*type_description).drop_glue)(object)));
+                if let Err(payload) = dropped {
+                    error!("The destructor of the data lying at byte {} panicked !", info.offset);
+                    if result.is_ok() {
+                        result = Err(payload);
+                    }
+                }
             }
+        }
+
+        self.destroying.set(false);
+        result
+    }
+
+    /// Returns whether or not the given index is the location of a header, or the first unused byte of the chunk.
+    ///
+    /// The footers are walked down from the top of the stack: only the first byte of an allocation is accepted,
+    /// an index lying inside an object is not. The chunk must hold data implementing the `Drop` trait.
+    pub(crate) unsafe fn is_allocation_boundary(&self, marker: usize) -> bool {
+        trace!("Checking if the byte {} is the start of an allocation in the memory chunk.", marker);
+        let mut index = self.fill.get();
+        if marker > index {
+            return false;
+        }
+
+        while index > marker {
+            let footer = self.as_ptr().offset((index - mem::size_of::<utils::AllocationFooter>()) as isize)
+                as *const utils::AllocationFooter;
+            index = (*footer).header;
+        }
+
+        index == marker
+    }
+
+    /// Reads the header lying at the given index, and returns informations about the object it describes.
+    ///
+    /// The given index must be the index of a header, lying before the first unused byte of the chunk.
+    pub(crate) unsafe fn allocation_info(&self, index: usize) -> AllocationInfo {
+        trace!("Reading the header lying at byte {}.", index);
+        //Get a raw pointer on the header of the object.
+        trace!("Getting a raw pointer to the memory location of the header.");
+        let header = self.as_ptr().offset(index as isize) as *const utils::AllocationHeader;
+
+        //Decode the TypeDescription pointer of the header to obtain the vtable of the object, and a boolean to know if
+        //the object has been initialized.
+        trace!("Unpacking the memory location to get the TypeDescription and its 'is_done' state.");
+        let (type_description, is_done) =
+            utils::un_bitpack_type_description_ptr((*header).type_description);
+
+        //Get the size and the alignment of the object, with its type description.
+        trace!("Getting the size and the alignment of the data.");
+        let (size, alignment) = ((*type_description).size, (*type_description).alignment);
+        trace!("size: {}, align: {}, type: {:?}", size, alignment, (*header).type_name());
+
+        //The locations below did not overflow when the object was allocated, they cannot overflow now.
+
+        //Get the index of the memory address just after the header.
+        //It's the unaligned memory address of the object.
+        trace!("Getting the index of the memory location just after this header (unaligned memory location of the data).");
+        let after_header = index + mem::size_of::<utils::AllocationHeader>();
+        trace!("unaligned memory location: {:x}", after_header);
+
+        //Get the aligned memory address, with the unaligned one and the alignment of the object.
+        //This is where the object *really* lives.
+        trace!("Getting the aligned memory location of the data.");
+        let start = utils::round_up(after_header, alignment).unwrap();
+        trace!("aligned memory location: {:x}", start);
+
+        //Find where the footer lives.
+        trace!("Finding the footer.");
+        let footer = utils::round_up(
+            start + size,
+            mem::align_of::<utils::AllocationFooter>(),
+        ).unwrap();
+        trace!("footer: {:x}", footer);
+
+        //The next header lives just after the footer.
+        let end = footer + mem::size_of::<utils::AllocationFooter>();
+        trace!("next header: {:x}", end);
 
-            //Find where the next type description lives.
-            trace!("Finding the next TypeDescription.");
-            index = utils::round_up(
-                start + size,
-                mem::align_of::<*const utils::TypeDescription>(),
-            );
-            trace!("next TypeDescription: {:x}", index);
+        AllocationInfo {
+            header: index,
+            offset: start,
+            size,
+            alignment,
+            footer,
+            end,
+            is_initialized: is_done,
+            type_name: (*header).type_name(),
         }
     }
 }
@@ -0,0 +1,341 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::Write;
+use std::mem;
+
+use memory_chunk::{AllocationInfo, Allocations};
+use utils;
+
+/// The formats in which the memory map of an allocator can be dumped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// A human-readable table, one line per region of memory.
+    Text,
+    /// A JSON document, listing the regions of memory of every memory storage.
+    Json,
+    /// An SVG image, drawing every memory storage as a strip of regions.
+    Svg,
+}
+
+/// What a region of a memory storage is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// The header placed before an object implementing the `Drop` trait.
+    Header,
+    /// The footer placed after an object implementing the `Drop` trait.
+    Footer,
+    /// Bytes lost to align an object or a header.
+    Padding,
+    /// An object.
+    Object,
+    /// Bytes used by objects implementing the `Copy` trait. Their boundaries are not recorded.
+    Used,
+    /// Bytes between the first unused byte and the end of the memory storage.
+    Free,
+}
+
+impl RegionKind {
+    fn name(&self) -> &'static str {
+        match *self {
+            RegionKind::Header => "header",
+            RegionKind::Footer => "footer",
+            RegionKind::Padding => "padding",
+            RegionKind::Object => "object",
+            RegionKind::Used => "used",
+            RegionKind::Free => "free",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match *self {
+            RegionKind::Header => "#f0ad4e",
+            RegionKind::Footer => "#f7d08a",
+            RegionKind::Padding => "#d9534f",
+            RegionKind::Object => "#5cb85c",
+            RegionKind::Used => "#5bc0de",
+            RegionKind::Free => "#eeeeee",
+        }
+    }
+}
+
+/// A contiguous region of a memory storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub kind: RegionKind,
+    /// Index of the first byte of the region.
+    pub start: usize,
+    /// Index of the first byte after the region.
+    pub end: usize,
+    /// The name of the type of the object, if the region is an object and its type name has been recorded.
+    pub type_name: Option<&'static str>,
+    /// Whether or not the object has been initialized, if the region is an object.
+    pub is_initialized: bool,
+}
+
+impl Region {
+    fn new(kind: RegionKind, start: usize, end: usize) -> Self {
+        Region {
+            kind,
+            start,
+            end,
+            type_name: None,
+            is_initialized: false,
+        }
+    }
+}
+
+/// The layout of a memory storage: its regions and its marker.
+#[derive(Debug, Clone)]
+pub struct StorageLayout {
+    pub name: &'static str,
+    pub capacity: usize,
+    /// Index of the first unused byte of the memory storage, the top of the stack.
+    pub marker: usize,
+    pub regions: Vec<Region>,
+}
+
+impl StorageLayout {
+    /// Creates the layout of a memory storage holding data implementing the `Drop` trait, from its objects.
+    pub fn from_allocations(name: &'static str, capacity: usize, marker: usize, allocations: Allocations) -> Self {
+        trace!("Creating the layout of the memory storage {}.", name);
+        let mut regions = Vec::new();
+
+        for info in allocations {
+            push_allocation(&mut regions, &info);
+        }
+
+        if marker < capacity {
+            regions.push(Region::new(RegionKind::Free, marker, capacity));
+        }
+
+        StorageLayout {
+            name,
+            capacity,
+            marker,
+            regions,
+        }
+    }
+
+    /// Creates the layout of a memory storage holding data implementing the `Copy` trait.
+    pub fn from_marker(name: &'static str, capacity: usize, marker: usize) -> Self {
+        trace!("Creating the layout of the memory storage {}.", name);
+        let mut regions = Vec::new();
+
+        if marker > 0 {
+            regions.push(Region::new(RegionKind::Used, 0, marker));
+        }
+        if marker < capacity {
+            regions.push(Region::new(RegionKind::Free, marker, capacity));
+        }
+
+        StorageLayout {
+            name,
+            capacity,
+            marker,
+            regions,
+        }
+    }
+}
+
+fn push_allocation(regions: &mut Vec<Region>, info: &AllocationInfo) {
+    let after_header = info.header + mem::size_of::<utils::AllocationHeader>();
+    let after_object = info.offset + info.size;
+
+    regions.push(Region::new(RegionKind::Header, info.header, after_header));
+    if after_header < info.offset {
+        regions.push(Region::new(RegionKind::Padding, after_header, info.offset));
+    }
+    regions.push(Region {
+        kind: RegionKind::Object,
+        start: info.offset,
+        end: after_object,
+        type_name: info.type_name,
+        is_initialized: info.is_initialized,
+    });
+    if after_object < info.footer {
+        regions.push(Region::new(RegionKind::Padding, after_object, info.footer));
+    }
+    regions.push(Region::new(RegionKind::Footer, info.footer, info.end));
+}
+
+/// Renders the layouts of the given memory storages in the given format.
+pub fn render(storages: &[StorageLayout], format: DumpFormat) -> String {
+    debug!("Rendering the memory map of {} memory storages.", storages.len());
+    let mut output = String::new();
+    //Writing in a String never fails.
+    match format {
+        DumpFormat::Text => render_text(&mut output, storages),
+        DumpFormat::Json => render_json(&mut output, storages),
+        DumpFormat::Svg => render_svg(&mut output, storages),
+    }.unwrap();
+    output
+}
+
+fn render_text(output: &mut String, storages: &[StorageLayout]) -> ::std::fmt::Result {
+    for storage in storages.iter() {
+        writeln!(
+            output,
+            "{} storage: {} / {} bytes used",
+            storage.name, storage.marker, storage.capacity
+        )?;
+        writeln!(output, "{:>10} {:>10} {:>10}  region", "start", "end", "size")?;
+
+        for region in storage.regions.iter() {
+            write!(
+                output,
+                "{:>10} {:>10} {:>10}  {}",
+                region.start,
+                region.end,
+                region.end - region.start,
+                region.kind.name()
+            )?;
+            if region.kind == RegionKind::Object {
+                if !region.is_initialized {
+                    write!(output, " (uninitialized)")?;
+                }
+                if let Some(type_name) = region.type_name {
+                    write!(output, " {}", type_name)?;
+                }
+            }
+            writeln!(output)?;
+        }
+
+        writeln!(output, "{:>10} {:>10} {:>10}  marker", storage.marker, "", "")?;
+    }
+    Ok(())
+}
+
+fn render_json(output: &mut String, storages: &[StorageLayout]) -> ::std::fmt::Result {
+    write!(output, "{{\"storages\":[")?;
+    for (i, storage) in storages.iter().enumerate() {
+        if i > 0 {
+            write!(output, ",")?;
+        }
+        write!(
+            output,
+            "{{\"name\":\"{}\",\"capacity\":{},\"marker\":{},\"regions\":[",
+            storage.name, storage.capacity, storage.marker
+        )?;
+
+        for (j, region) in storage.regions.iter().enumerate() {
+            if j > 0 {
+                write!(output, ",")?;
+            }
+            write!(
+                output,
+                "{{\"kind\":\"{}\",\"start\":{},\"end\":{}",
+                region.kind.name(),
+                region.start,
+                region.end
+            )?;
+            if region.kind == RegionKind::Object {
+                write!(output, ",\"initialized\":{},\"type_name\":", region.is_initialized)?;
+                match region.type_name {
+                    Some(type_name) => write_json_string(output, type_name)?,
+                    None => write!(output, "null")?,
+                }
+            }
+            write!(output, "}}")?;
+        }
+        write!(output, "]}}")?;
+    }
+    write!(output, "]}}")
+}
+
+fn write_json_string(output: &mut String, value: &str) -> ::std::fmt::Result {
+    write!(output, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(output, "\\\"")?,
+            '\\' => write!(output, "\\\\")?,
+            c if (c as u32) < 0x20 => write!(output, "\\u{:04x}", c as u32)?,
+            c => write!(output, "{}", c)?,
+        }
+    }
+    write!(output, "\"")
+}
+
+const SVG_WIDTH: usize = 800;
+const SVG_STRIP_HEIGHT: usize = 40;
+const SVG_LABEL_HEIGHT: usize = 20;
+
+fn render_svg(output: &mut String, storages: &[StorageLayout]) -> ::std::fmt::Result {
+    let row_height = SVG_LABEL_HEIGHT + SVG_STRIP_HEIGHT + SVG_LABEL_HEIGHT;
+    writeln!(
+        output,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">",
+        SVG_WIDTH,
+        row_height * storages.len()
+    )?;
+
+    for (i, storage) in storages.iter().enumerate() {
+        let label_y = i * row_height + SVG_LABEL_HEIGHT - 5;
+        let strip_y = i * row_height + SVG_LABEL_HEIGHT;
+        //Scale the memory storage to the width of the image.
+        let scale = if storage.capacity == 0 {
+            0.0
+        } else {
+            SVG_WIDTH as f64 / storage.capacity as f64
+        };
+
+        writeln!(
+            output,
+            "<text x=\"0\" y=\"{}\">{} storage: {} / {} bytes used</text>",
+            label_y, storage.name, storage.marker, storage.capacity
+        )?;
+
+        for region in storage.regions.iter() {
+            if region.end == region.start {
+                continue;
+            }
+            write!(
+                output,
+                "<rect x=\"{:.2}\" y=\"{}\" width=\"{:.2}\" height=\"{}\" fill=\"{}\" stroke=\"#333333\" stroke-width=\"0.5\"><title>{} [{}, {})",
+                region.start as f64 * scale,
+                strip_y,
+                (region.end - region.start) as f64 * scale,
+                SVG_STRIP_HEIGHT,
+                region.kind.color(),
+                region.kind.name(),
+                region.start,
+                region.end
+            )?;
+            if let Some(type_name) = region.type_name {
+                write!(output, " ")?;
+                write_xml_string(output, type_name)?;
+            }
+            writeln!(output, "</title></rect>")?;
+        }
+
+        let marker_x = storage.marker as f64 * scale;
+        writeln!(
+            output,
+            "<line x1=\"{:.2}\" y1=\"{}\" x2=\"{:.2}\" y2=\"{}\" stroke=\"#000000\" stroke-width=\"2\"><title>marker {}</title></line>",
+            marker_x,
+            strip_y,
+            marker_x,
+            strip_y + SVG_STRIP_HEIGHT + 5,
+            storage.marker
+        )?;
+    }
+
+    writeln!(output, "</svg>")
+}
+
+fn write_xml_string(output: &mut String, value: &str) -> ::std::fmt::Result {
+    for c in value.chars() {
+        match c {
+            '<' => write!(output, "&lt;")?,
+            '>' => write!(output, "&gt;")?,
+            '&' => write!(output, "&amp;")?,
+            '"' => write!(output, "&quot;")?,
+            c => write!(output, "{}", c)?,
+        }
+    }
+    Ok(())
+}
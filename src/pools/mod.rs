@@ -5,5 +5,4 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-mod pool_item;
 pub mod pool_allocator;
@@ -5,601 +5,331 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-/*
-
-use std::cell::{RefCell, Cell};
+use std::cell::Cell;
+use std::cmp;
 use std::mem;
 use std::ptr;
-use utils;
-use allocation_error::{AllocationResult, AllocationError};
+
+use allocation_error::{AllocationError, AllocationResult};
+use memory_chunk::MemoryChunk;
 use smart_pointers::unique_ptr::UniquePtr;
-use smart_pointers::shared_ptr::{SharedUnique, SharedPtr};
-use pools::pool_item::PoolItem;
-use std::intrinsics::needs_drop;
 
+/// The largest alignment of the blocks of a pool, in bytes.
+///
+/// The first block is aligned on this value, the other blocks are aligned on the largest power of two dividing
+/// the size of a block, up to this value.
+const MAX_BLOCK_ALIGNMENT: usize = 16;
+
+/// A pool allocator, handing out fixed-size blocks of a single memory storage.
+///
+/// The blocks are allocated and freed in any order, in constant time. The free blocks are linked together: every free
+/// block holds a pointer to the next free block, the pool doesn't need any other memory to track them.
+///
+/// The data allocated in the pool is owned by a `UniquePtr`, which drops the data and gives its block back to the pool
+/// when it goes out of scope.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::PoolAllocator;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// // 2 blocks of 32 bytes.
+/// let pool = PoolAllocator::new(2, 32);
+///
+/// let monster = pool.alloc(|| String::from("goblin"))?;
+/// {
+///     let another_monster = pool.alloc(|| String::from("dragon"))?;
+///     assert_eq!(pool.available(), 0);
+///
+///     // All the blocks are in use.
+///     assert!(pool.alloc(|| String::from("troll")).is_err());
+/// }
+///
+/// // The dragon has been dropped, its block is available again.
+/// assert_eq!(pool.available(), 1);
+/// let another_monster = pool.alloc(|| String::from("troll"))?;
+/// assert_eq!(*monster, "goblin");
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
 pub struct PoolAllocator {
-    storage: Vec<RefCell<PoolItem>>,
-    storage_copy: Vec<RefCell<PoolItem>>,
-    first_available: Cell<Option<usize>>,
-    first_available_copy: Cell<Option<usize>>,
+    storage: MemoryChunk,
+    /// The first block, aligned on `MAX_BLOCK_ALIGNMENT`.
+    blocks: *mut u8,
+    block_size: usize,
+    block_alignment: usize,
+    block_count: usize,
+    /// The first free block, or a null pointer if all the blocks are in use.
+    first_available: Cell<*mut u8>,
+    /// The number of free blocks.
+    available: Cell<usize>,
 }
 
 impl PoolAllocator {
-    /// Creates a poolAllocator with `nb_item` chunks of `size_item` size in byte.
-    pub fn new(nb_item: usize, size_item: usize, nb_item_copy: usize, size_item_copy: usize) -> Self {
-        let mut storage = Vec::with_capacity(nb_item);
-        for i in 0..nb_item - 1 {
-            storage.push(RefCell::new(PoolItem::new(size_item, Some(i+1))));
-        }
-        storage.push(RefCell::new(PoolItem::new(size_item, None)));
-
-        let mut storage_copy = Vec::with_capacity(nb_item_copy);
-        for i in 0..nb_item_copy - 1 {
-            storage_copy.push(RefCell::new(PoolItem::new(size_item_copy, Some(i+1))));
-        }
-        storage_copy.push(RefCell::new(PoolItem::new(size_item_copy, None)));
-
-        PoolAllocator {
-            storage,
-            storage_copy,
-            first_available: Cell::new(Some(0)),
-            first_available_copy: Cell::new(Some(0)),
-        }
-    }
-
-    /// Returns an immutable slice of the vector of memory chunks used by the allocator.
-    pub fn storage(&self) -> &[RefCell<PoolItem>] {
-        &self.storage
-    }
-
-    pub fn storage_copy(&self) -> &[RefCell<PoolItem>] {
-        &self.storage_copy
-    }
-
-    /// Returns the index of the first available pool item in the pool allocator.
-    pub fn first_available(&self) -> Option<usize> {
-        self.first_available.get()
-    }
-
-    pub fn first_available_copy(&self) -> Option<usize> {
-        self.first_available_copy.get()
-    }
+    /// Creates a PoolAllocator with `block_count` blocks of at least `block_size` bytes.
+    ///
+    /// A block must be able to hold a pointer to the next free block: its size is rounded up to a multiple of the size
+    /// of a pointer.
+    ///
+    /// # Panic
+    /// This function will panic if the size of the memory storage overflows.
+    pub fn new(block_count: usize, block_size: usize) -> Self {
+        debug!("Creating a pool allocator with {} blocks of {} bytes.", block_count, block_size);
+        let pointer_size = mem::size_of::<*mut u8>();
+        let block_size = cmp::max(block_size, 1)
+            .checked_add(pointer_size - 1)
+            .expect("The size of a block of the pool overflows.") / pointer_size * pointer_size;
+        //The lowest set bit of the block size is the largest power of two dividing it.
+        let block_alignment = cmp::min(MAX_BLOCK_ALIGNMENT, block_size & block_size.wrapping_neg());
+
+        //The memory chunk may not be aligned, some bytes are kept to align the first block.
+        let capacity = block_count
+            .checked_mul(block_size)
+            .and_then(|size| size.checked_add(MAX_BLOCK_ALIGNMENT - 1))
+            .expect("The size of the memory storage of the pool overflows.");
+        let storage = MemoryChunk::new(capacity);
 
-    /// Sets the index of the first available pool item in the pool allocator.
-    pub fn set_first_available(&self, first_available: Option<usize>) {
-        self.first_available.set(first_available);
-    }
-
-    pub fn set_first_available_copy(&self, first_available: Option<usize>) {
-        self.first_available_copy.set(first_available)
-    }
-
-    /// Allocates data in the pool allocator, returning a `SharedPtr`.
-    /// # Errors
-    /// This function will return an error if all the pools are used when trying to allocate this data.
-    pub fn alloc_shared<T, F>(&self, op: F) -> AllocationResult<SharedPtr<T>>
-        where F: FnOnce() -> T
-    {
         unsafe {
-            if needs_drop::<T>() {
-                self.alloc_non_copy_shared(op)
-            } else {
-                self.alloc_copy_shared(op)
+            let start = storage.as_ptr() as *mut u8;
+            let blocks = start.offset(((MAX_BLOCK_ALIGNMENT - start as usize % MAX_BLOCK_ALIGNMENT) % MAX_BLOCK_ALIGNMENT) as isize);
+
+            trace!("Linking the {} free blocks of the pool allocator.", block_count);
+            for index in 0..block_count {
+                let block = blocks.offset((index * block_size) as isize);
+                let next = if index + 1 < block_count {
+                    block.offset(block_size as isize)
+                } else {
+                    ptr::null_mut()
+                };
+                ptr::write(block as *mut *mut u8, next);
             }
-        }
-
-    }
 
-    fn alloc_copy_shared<T, F>(&self, op: F) -> AllocationResult<SharedPtr<T>>
-        where F: FnOnce() -> T
-    {
-        unsafe {
-            match self.first_available_copy() {
-                Some(index) => {
-                    //Get an aligned raw pointer to place the object in it.
-                    let ptr = self.alloc_copy_inner(index,mem::size_of::<SharedUnique<T>>(), mem::align_of::<SharedUnique<T>>())?;
-
-                    //cast this raw pointer to the type of the object.
-                    let ptr = ptr as *mut SharedUnique<T>;
-
-                    //Write the data in the memory location.
-                    ptr::write(&mut (*ptr), SharedUnique{strong: Cell::new(1), weak: Cell::new(1), value: op()});
-
-
-                    Ok(SharedPtr::from_raw(ptr, &self, index, false))
-                },
-                None => {
-                    return Err(AllocationError::OutOfPoolError(format!("All the pools in the copy storage were in use when the allocation was requested !")));
-                }
+            PoolAllocator {
+                storage,
+                blocks,
+                block_size,
+                block_alignment,
+                block_count,
+                first_available: Cell::new(if block_count > 0 { blocks } else { ptr::null_mut() }),
+                available: Cell::new(block_count),
             }
         }
     }
 
-
-    fn alloc_non_copy_shared<T, F>(&self, op: F) -> AllocationResult<SharedPtr<T>>
-        where F: FnOnce() -> T
+    /// Allocates data in a free block of the pool, returning a `UniquePtr` owning the data.
+    ///
+    /// # Error
+    /// This function will return an error if all the blocks are in use, or if the data doesn't fit in a block.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::PoolAllocator;
+    /// use maskerad_memory_allocators::allocation_error::AllocationError;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let pool = PoolAllocator::new(10, 8);
+    ///
+    /// let mut counter = pool.alloc(|| 0 as u64)?;
+    /// *counter += 1;
+    /// assert_eq!(*counter, 1);
+    ///
+    /// // 16 bytes don't fit in a block of 8 bytes.
+    /// match pool.alloc(|| [0 as u64; 2]) {
+    ///     Err(AllocationError::BlockSizeError { block_size, .. }) => assert_eq!(block_size, 8),
+    ///     _ => panic!("The data should not fit in a block."),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<UniquePtr<T>>
+    where
+        F: FnOnce() -> T,
     {
-        unsafe {
-
-
-            //Get the type description of the type T (get its vtable).
-            let type_description = utils::get_type_description::<SharedUnique<T>>();
-
-            //Get the index of the current first available pool item in the pool allocator.
-            //alloc_non_copy_inner will update the index of the first available pool item,
-            //and we need this index to create an UniquePtr.
-            match self.first_available() {
-                Some(index) => {
-                    //Ask the the first available memory chunk to give us raw pointers to memory locations
-                    //for our type description object.
-                    let (type_description_ptr, ptr) = self.alloc_non_copy_inner(index, mem::size_of::<SharedUnique<T>>(), mem::align_of::<SharedUnique<T>>())?;
-
-                    //Cast them.
-                    let type_description_ptr = type_description_ptr as *mut usize;
-                    let ptr = ptr as *mut SharedUnique<T>;
+        debug!("Allocating data in a block of the pool allocator.");
+        if mem::size_of::<T>() > self.block_size || mem::align_of::<T>() > self.block_alignment {
+            error!("The data doesn't fit in a block of the pool allocator !");
+            return Err(AllocationError::BlockSizeError {
+                requested_size: mem::size_of::<T>(),
+                alignment: mem::align_of::<T>(),
+                block_size: self.block_size,
+                block_alignment: self.block_alignment,
+            });
+        }
 
-                    //Write in our type description along with a bit indicating that the object has *not*
-                    //been initialized yet.
-                    *type_description_ptr = utils::bitpack_type_description_ptr(type_description, false);
+        //The initializer doesn't run if the pool is full.
+        self.check_available()?;
 
-                    //Initialize the object.
-                    ptr::write(&mut (*ptr), SharedUnique{strong: Cell::new(1), weak: Cell::new(1), value: op()});
+        //The data is created before a block is taken: the initializer can use the pool, or panic, without losing a block.
+        let value = op();
 
-                    //Now that we are done, update the type description to indicate that the object is there.
-                    *type_description_ptr = utils::bitpack_type_description_ptr(type_description, true);
+        //The initializer may have taken the last free blocks.
+        self.check_available()?;
+        let block = self.first_available.get();
 
-                    Ok(SharedPtr::from_raw(ptr, &self, index, true))
+        unsafe {
+            trace!("Taking the block at {:p} from the free list.", block);
+            self.first_available.set(ptr::read(block as *const *mut u8));
+            self.available.set(self.available.get() - 1);
 
-                },
-                None => {
-                    return Err(AllocationError::OutOfPoolError(format!("All the pools in the non-copy storage were in use when the allocation was requested !")));
-                }
-            }
+            let data = block as *mut T;
+            ptr::write(data, value);
+            Ok(UniquePtr::from_raw(data, self))
         }
     }
 
-    /// Allocates data in the pool allocator, returning an `UniquePtr`.
-    /// # Errors
-    /// This function will return an error if all the pools are used when trying to allocate this data.
-    #[inline]
-    pub fn alloc_unique<F, T>(&self, op: F) -> AllocationResult<UniquePtr<T>>
-        where F: FnOnce() -> T
-    {
-        unsafe {
-            if needs_drop::<T>() {
-                self.alloc_non_copy_unique(op)
-            } else {
-                self.alloc_copy_unique(op)
-            }
+    /// Checks that a block is free.
+    fn check_available(&self) -> AllocationResult<()> {
+        if self.first_available.get().is_null() {
+            error!("All the blocks of the pool allocator are in use !");
+            return Err(AllocationError::OutOfPoolError {
+                block_size: self.block_size,
+                block_count: self.block_count,
+            });
         }
 
+        Ok(())
     }
 
-    fn alloc_copy_unique<T, F>(&self, op: F) -> AllocationResult<UniquePtr<T>>
-        where F: FnOnce() -> T
-    {
-        unsafe {
-            match self.first_available_copy() {
-                Some(index) => {
-                    //Get an aligned raw pointer to place the object in it.
-                    let ptr = self.alloc_copy_inner(index,mem::size_of::<T>(), mem::align_of::<T>())?;
-
-                    //cast this raw pointer to the type of the object.
-                    let ptr = ptr as *mut T;
-
-                    //Write the data in the memory location.
-                    ptr::write(&mut (*ptr), op());
-
-
-                    Ok(UniquePtr::from_raw(ptr, &self, index, false))
-                },
-                None => {
-                    return Err(AllocationError::OutOfPoolError(format!("All the pools in the copy storage were in use when the allocation was requested !")));
-                }
-            }
-        }
+    /// Gives a block back to the pool. The data it holds must have been dropped.
+    pub(crate) unsafe fn free(&self, block: *mut u8) {
+        trace!("Giving the block at {:p} back to the free list.", block);
+        debug_assert!(self.owns(block), "The block doesn't belong to the pool allocator.");
+        ptr::write(block as *mut *mut u8, self.first_available.get());
+        self.first_available.set(block);
+        self.available.set(self.available.get() + 1);
     }
 
-    #[inline]
-    fn alloc_non_copy_unique<T, F>(&self, op: F) -> AllocationResult<UniquePtr<T>>
-        where F: FnOnce() -> T
-    {
-        unsafe {
-            //Get the type description of the type T (get its vtable).
-            let type_description = utils::get_type_description::<T>();
-
-            //Get the index of the current first available pool item in the pool allocator.
-            //alloc_non_copy_inner will update the index of the first available pool item,
-            //and we need this index to create an UniquePtr.
-            match self.first_available() {
-                Some(index) => {
-                    //Ask the the first available memory chunk to give us raw pointers to memory locations
-                    //for our type description object.
-                    let (type_description_ptr, ptr) = self.alloc_non_copy_inner(index, mem::size_of::<T>(), mem::align_of::<T>())?;
-
-                    //Cast them.
-                    let type_description_ptr = type_description_ptr as *mut usize;
-                    let ptr = ptr as *mut T;
-
-                    //Write in our type description along with a bit indicating that the object has *not*
-                    //been initialized yet.
-                    *type_description_ptr = utils::bitpack_type_description_ptr(type_description, false);
-
-                    //Initialize the object.
-                    ptr::write(&mut (*ptr), op());
-
-                    //Now that we are done, update the type description to indicate that the object is there.
-                    *type_description_ptr = utils::bitpack_type_description_ptr(type_description, true);
-
-                    Ok(UniquePtr::from_raw(ptr, &self, index, true))
-
-                },
-                None => {
-                    return Err(AllocationError::OutOfPoolError(format!("All the pools in the non-copy storage were in use when the allocation was requested !")));
-                }
-            }
-        }
+    /// Returns whether or not the pointer designates the start of a block of the pool.
+    fn owns(&self, block: *mut u8) -> bool {
+        let start = self.blocks as usize;
+        let end = start + self.block_count * self.block_size;
+        let address = block as usize;
+        address >= start && address < end && (address - start) % self.block_size == 0
     }
 
-    #[inline]
-    fn alloc_non_copy_inner(&self, chunk_index: usize, n_bytes: usize, align: usize) -> AllocationResult<(*const u8, *const u8)> {
-
-        //Borrow mutably the first pool item available in the pool allocator.
-        let non_copy_storage = self.storage().get(chunk_index).unwrap().borrow();
-
-        //This chunk of memory is now in use, update the index of the first available chunk of memory.
-        self.first_available.set(non_copy_storage.next());
-
-        //Get the index of the first unused memory address.
-        let fill = non_copy_storage.memory_chunk().fill();
-
-        //Get the index of where we'll write the type description data
-        //(the first unused byte in the memory chunk)
-        let type_description_start = fill;
-
-        //Get the index of where the object should reside (unaligned location actually).
-        let after_type_description = fill + mem::size_of::<*const utils::TypeDescription>();
-
-        //With the index to the unaligned memory address, determine the index to the aligned
-        //memory address where the object will reside,
-        //according to its memory alignment.
-        let start = utils::round_up(after_type_description, align);
-
-        //Determine the index of the next aligned memory address for a type description,
-        //according to the size of the object and the memory alignment of a type description.
-        let end = utils::round_up(start + n_bytes, mem::align_of::<*const utils::TypeDescription>());
-
-        //If the allocator become oom after this possible allocation, abort the program.
-        if end >= non_copy_storage.memory_chunk().capacity() {
-            return Err(AllocationError::OutOfMemoryError(format!("memory chunks of the non-copy storage don't have enough memory to hold this type !")));
-        }
-
-        //Update the current top of the stack. The first unused memory address is at
-        //index 'end', where the next type description would be written if an allocation
-        //was asked.
-        non_copy_storage.memory_chunk().set_fill(end);
-
-        unsafe {
-            //Get a raw pointer to the start of the RawVec of the MemoryChunk of the PoolItem. Yep.
-            let start_storage = non_copy_storage.memory_chunk().as_ptr();
-
-            Ok(
-                (
-                    //From this raw pointer, get the correct raw pointers with the indices
-                    //we calculated earlier.
-
-                    //The raw pointer to the type description of the object.
-                    start_storage.offset(type_description_start as isize),
-
-                    //The raw pointer to the object
-                    start_storage.offset(start as isize)
-                ))
-
-        }
+    /// Returns the size of a block, in bytes.
+    pub fn block_size(&self) -> usize {
+        self.block_size
     }
 
-    fn alloc_copy_inner(&self, chunk_index: usize, n_bytes: usize, align: usize) -> AllocationResult<*const u8> {
-        //borrow mutably the memory chunk used by the allocator.
-        let copy_storage = self.storage_copy().get(chunk_index).unwrap().borrow();
-
-        //This chunk of memory is now in use, update the index of the first available chunk of memory.
-        self.first_available_copy.set(copy_storage.next());
-
-        //Get the index of the first unused memory address.
-        let fill = copy_storage.memory_chunk().fill();
-
-        //Get the index of the aligned memory address, which will be returned.
-        let start = utils::round_up(fill, align);
-
-        //Get the index of the future first unused memory address, according to the size of the object.
-        let end = start + n_bytes;
+    /// Returns the alignment of the blocks, in bytes.
+    pub fn block_alignment(&self) -> usize {
+        self.block_alignment
+    }
 
-        //We don't grow the capacity, or create another chunk.
-        if end >= copy_storage.memory_chunk().capacity() {
-            return Err(AllocationError::OutOfMemoryError(format!("memory chunks of the copy storage don't have enough memory to hold this type !")));
-        }
+    /// Returns the number of blocks of the pool.
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
 
-        //Set the first unused memory address of the memory chunk to the index calculated earlier.
-        copy_storage.memory_chunk().set_fill(end);
+    /// Returns the number of free blocks.
+    pub fn available(&self) -> usize {
+        self.available.get()
+    }
 
-        unsafe {
-            //Return the raw pointer to the aligned memory location, which will be used to place
-            //the object in the allocator.
-            Ok(copy_storage.memory_chunk().as_ptr().offset(start as isize))
-        }
+    /// Returns the capacity of the memory storage of the pool, in bytes.
+    pub fn capacity(&self) -> usize {
+        self.storage.capacity()
     }
 }
 
 #[cfg(test)]
 mod pool_allocator_test {
     use super::*;
-    use std::mem::drop;
-    use smart_pointers::shared_ptr::WeakPtr;
+    use std::rc::Rc;
 
-    //size : 4 bytes + 4 bytes alignment + 4 bytes + 4 bytes alignment + alignment-offset stuff -> ~16-20 bytes.
-#[derive(Clone)]
     struct Monster {
-    _hp: u32,
-}
-
-    impl Monster {
-        pub fn new(hp: u32) -> Self {
-            Monster {
-                _hp: hp,
-            }
-        }
-
-        pub fn level(&self) -> u32 {
-            self._hp
-        }
-    }
-
-    impl Default for Monster {
-        fn default() -> Self {
-            Monster {
-                _hp: 1,
-            }
-        }
+        level: u32,
+        deaths: Rc<Cell<u32>>,
     }
 
     impl Drop for Monster {
         fn drop(&mut self) {
-            println!("[PoolAllocator] {} I'm dying !", self._hp);
+            self.deaths.set(self.deaths.get() + 1);
         }
     }
 
-    //1, 2, 3
     #[test]
-    fn test_unique_ptr_drop_and_nb_pool_available() {
-        //create a pool allocator with 2 pool items of 100 bytes.
-        let pool = PoolAllocator::new(2, 100, 2, 100);
-
-        //the index of the first available pool item is 0.
-        assert_eq!(pool.first_available.get(), Some(0));
-
-        //Create a UniquePtr from the pool allocator
-        let a_monster = pool.alloc_unique(|| {
-            Monster::new(3)
-        }).unwrap();
-        assert_eq!(a_monster.level(), 3);
-        //The index of the first available pool item is 1.
-        assert_eq!(pool.first_available.get(), Some(1));
-
-        {
-            //The first pool item is used, allocate a new monster in the second pool item.
-            let another_monster = pool.alloc_unique(|| {
-                Monster::new(1)
-            }).unwrap();
-
-            //The index of the first available item is None. There's no pool items available.
-            assert_eq!(pool.first_available.get(), None);
-            assert_eq!(another_monster.level(), 1);
-            //Since no pool items are available, we'll get an error if we try to allocate something.
-            /*
-            assert!(pool.alloc_unique(|| {
-                Monster::default()
-            }).is_err());
-            */
-
-            //another_monster will be dropped and should print "i'm dying !".
+    fn free_list() {
+        let pool = PoolAllocator::new(3, mem::size_of::<Monster>());
+        let deaths = Rc::new(Cell::new(0));
+        assert_eq!(pool.block_size() % mem::size_of::<*mut u8>(), 0);
+        assert!(pool.block_alignment() >= mem::align_of::<Monster>());
+
+        let first = pool.alloc(|| Monster { level: 1, deaths: deaths.clone() }).unwrap();
+        let second = pool.alloc(|| Monster { level: 2, deaths: deaths.clone() }).unwrap();
+        let third = pool.alloc(|| Monster { level: 3, deaths: deaths.clone() }).unwrap();
+        assert_eq!(pool.available(), 0);
+
+        //The blocks are contiguous, in a single memory storage.
+        let first_address = &*first as *const Monster as usize;
+        assert_eq!(&*second as *const Monster as usize, first_address + pool.block_size());
+        assert_eq!(first_address % pool.block_alignment(), 0);
+
+        match pool.alloc(|| 4 as u32) {
+            Err(AllocationError::OutOfPoolError { block_count: 3, .. }) => {}
+            _ => panic!("All the blocks should be in use."),
         }
 
-        //Since the monster has been dropped, the index of the first available pool item is 1.
-        assert_eq!(pool.first_available.get(), Some(1));
-        assert!(pool.alloc_unique(|| {
-            Monster::new(2)
-        }).is_ok())
+        //The last freed block is the first reused.
+        drop(second);
+        assert_eq!(deaths.get(), 1);
+        assert_eq!(pool.available(), 1);
+        let reused = pool.alloc(|| 5 as u32).unwrap();
+        assert_eq!(&*reused as *const u32 as usize, first_address + pool.block_size());
+
+        assert_eq!(first.level + third.level + *reused, 9);
+        drop(first);
+        drop(third);
+        drop(reused);
+        assert_eq!(deaths.get(), 3);
+        assert_eq!(pool.available(), 3);
     }
 
-    //4, 4, 5
     #[test]
-    fn test_unique_ptr_behavior() {
-        //Clone behavior and usage
-
-        //create a pool allocator with 2 pool items of 100 bytes.
-        let pool = PoolAllocator::new(2, 100, 2, 100);
-
-        //the index of the first available pool item is 0.
-        assert_eq!(pool.first_available.get(), Some(0));
-
-        let a_monster = pool.alloc_unique(|| {
-            Monster::new(4)
-        }).unwrap();
-        assert_eq!(a_monster.level(), 4);
-        assert_eq!(pool.first_available.get(), Some(1));
-
-        {
-            let a_monster_clone = a_monster.clone();
-            assert_eq!(a_monster_clone.level(), 4);
-            assert_eq!(pool.first_available.get(), None);
-            assert!(pool.alloc_unique(|| {
-                Monster::new(2)
-            }).is_err());
-
-            //a_monster_clone prints "i'm dying !"
-        }
-        assert_eq!(pool.first_available.get(), Some(1));
-        assert!(pool.alloc_unique(|| {
-            Monster::new(5)
-        }).is_ok());
-
-
+    fn oversized_data() {
+        let pool = PoolAllocator::new(2, 4);
+        assert_eq!(pool.block_size(), mem::size_of::<*mut u8>());
+        assert!(pool.alloc(|| [0 as u8; 64]).is_err());
+        assert_eq!(pool.available(), 2);
+
+        let empty = PoolAllocator::new(0, 8);
+        assert!(empty.alloc(|| 0 as u8).is_err());
     }
 
-    //6, 7
     #[test]
-    fn test_shared_ptr_behavior() {
-        //create a pool allocator with 2 pool items of 100 bytes.
-        let pool = PoolAllocator::new(2, 100, 2, 100);
-        //the index of the first available pool item is 0.
-        assert_eq!(pool.first_available.get(), Some(0));
-        //Create a SharedPtr from the pool.
-        let monster = pool.alloc_shared(|| {
-            Monster::new(6)
-        }).unwrap();
-        assert_eq!(pool.first_available.get(), Some(1));
-
-        //The strong count must be 1 (only one SharedPtr on this value)
-        //The weak count must be 0 (No weakPtr in this value, except the implicit weak count in the strong count)
-        assert_eq!(SharedPtr::strong_count(&monster), 1);
-        assert_eq!(SharedPtr::weak_count(&monster), 0);
-
-        //create a SharedPtr from the SharedPtr
-        let strong_ref_monster = SharedPtr::clone(&monster);
-        assert_eq!(pool.first_available.get(), Some(1));
-        assert_eq!(SharedPtr::strong_count(&monster), 2);
-        assert_eq!(SharedPtr::strong_count(&strong_ref_monster), 2);
-        assert_eq!(SharedPtr::weak_count(&monster), 0);
-        assert_eq!(SharedPtr::weak_count(&strong_ref_monster), 0);
-
-        {
-            //create a weak from the SharedPtr
-            let weak_ref_monster = SharedPtr::downgrade(&monster);
-            assert_eq!(pool.first_available.get(), Some(1));
-            assert_eq!(SharedPtr::strong_count(&monster), 2);
-            assert_eq!(SharedPtr::strong_count(&strong_ref_monster), 2);
-            assert_eq!(SharedPtr::weak_count(&monster), 1);
-            assert_eq!(SharedPtr::weak_count(&strong_ref_monster), 1);
-
-            //create another weak.
-            let weak_ref_monster_2 = SharedPtr::downgrade(&strong_ref_monster);
-            assert_eq!(pool.first_available.get(), Some(1));
-            assert_eq!(SharedPtr::strong_count(&monster), 2);
-            assert_eq!(SharedPtr::strong_count(&strong_ref_monster), 2);
-            assert_eq!(SharedPtr::weak_count(&monster), 2);
-            assert_eq!(SharedPtr::weak_count(&strong_ref_monster), 2);
-
-            //clone a weak.
-            let weak_ref_monster_3 = WeakPtr::clone(&weak_ref_monster_2);
-            assert_eq!(pool.first_available.get(), Some(1));
-            assert_eq!(SharedPtr::strong_count(&monster), 2);
-            assert_eq!(SharedPtr::strong_count(&strong_ref_monster), 2);
-            assert_eq!(SharedPtr::weak_count(&monster), 3);
-            assert_eq!(SharedPtr::weak_count(&strong_ref_monster), 3);
-        }
-        //all the weaks are dropped here.
-        assert_eq!(pool.first_available.get(), Some(1));
-        assert_eq!(SharedPtr::strong_count(&monster), 2);
-        assert_eq!(SharedPtr::strong_count(&strong_ref_monster), 2);
-        assert_eq!(SharedPtr::weak_count(&monster), 0);
-        assert_eq!(SharedPtr::weak_count(&strong_ref_monster), 0);
-
-
-        {
-            //create a new monster
-            let another_monster = pool.alloc_shared(|| {
-                Monster::new(7)
-            }).unwrap();
-            assert_eq!(pool.first_available.get(), None);
-            assert_eq!(SharedPtr::strong_count(&another_monster), 1);
-            assert_eq!(SharedPtr::weak_count(&another_monster), 0);
-
-            //create a weak with downgrade
-            let weak_ref_another_monster = SharedPtr::downgrade(&another_monster);
-            assert_eq!(pool.first_available.get(), None);
-            assert_eq!(SharedPtr::strong_count(&another_monster), 1);
-            assert_eq!(SharedPtr::weak_count(&another_monster), 1);
-
-            //create a strong with upgrade
-            assert!(WeakPtr::upgrade(&weak_ref_another_monster).is_some());
-            let strong_ref_another_monster = WeakPtr::upgrade(&weak_ref_another_monster).unwrap();
-            assert_eq!(pool.first_available.get(), None);
-            assert_eq!(SharedPtr::strong_count(&another_monster), 2);
-            assert_eq!(SharedPtr::strong_count(&strong_ref_another_monster), 2);
-            assert_eq!(SharedPtr::weak_count(&another_monster), 1);
-            assert_eq!(SharedPtr::weak_count(&strong_ref_another_monster), 1);
-        }
-        //All the strong refs to the second monster have been dropped, the second pool is available again.
-        assert_eq!(pool.first_available.get(), Some(1));
+    fn full_pool() {
+        let pool = PoolAllocator::new(1, 8);
+        let initialized = Cell::new(false);
+        let first = pool.alloc(|| 1 as u64).unwrap();
+
+        //The initializer doesn't run when all the blocks are in use.
+        let result = pool.alloc(|| {
+            initialized.set(true);
+            2 as u64
+        });
+        assert!(result.is_err());
+        assert!(!initialized.get());
+        drop(first);
+
+        //The initializer takes the last free block: its data is dropped, and the block stays in use.
+        let nested = Cell::new(None);
+        let result = pool.alloc(|| {
+            nested.set(Some(pool.alloc(|| 3 as u64).unwrap()));
+            4 as u64
+        });
+        assert!(result.is_err());
+        assert_eq!(pool.available(), 0);
+        assert_eq!(*nested.take().unwrap(), 3);
+        assert_eq!(pool.available(), 1);
     }
-
-    //8, 9
-    #[test]
-    fn test_trait_object_with_smart_ptr() {
-        pub trait TestTraitObject {
-            fn test(&self) -> Option<()>;
-        }
-
-        impl TestTraitObject for Monster {
-            fn test(&self) -> Option<()> {
-                Some(())
-            }
-        }
-
-        pub struct Dragon {
-            _hp: u32,
-        }
-
-        impl Dragon {
-            pub fn new(hp: u32) -> Self {
-                Dragon {
-                    _hp: hp,
-                }
-            }
-        }
-
-        impl Drop for Dragon {
-            fn drop(&mut self) {
-                println!("Dragon is dying !");
-            }
-        }
-
-        impl TestTraitObject for Dragon {
-            fn test(&self) -> Option<()> {
-                None
-            }
-        }
-
-        pub struct StructTest<'a> {
-            test: UniquePtr<'a, TestTraitObject>,
-        }
-
-        pub struct StructTestShared<'a> {
-            test: SharedPtr<'a, TestTraitObject>,
-        }
-
-
-        //create a pool allocator with 2 pool items of 100 bytes.
-        let pool = PoolAllocator::new(2, 100, 2, 100);
-        //the index of the first available pool item is 0.
-        assert_eq!(pool.first_available.get(), Some(0));
-
-        let struct_test = StructTest {
-            test: pool.alloc_unique(|| { Monster::new(8) }).unwrap() as UniquePtr<TestTraitObject>,
-        };
-
-        let struct_test_shared = StructTestShared {
-            test: pool.alloc_shared(|| {
-                Dragon::new(9)
-            }).unwrap() as SharedPtr<TestTraitObject>,
-        };
-
-        assert_eq!(pool.first_available.get(), None);
-
-        assert!(struct_test.test.test().is_some());
-        assert!(struct_test_shared.test.test().is_none());
-    }
-
 }
-
-*/
@@ -0,0 +1,309 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use allocation_error::AllocationResult;
+use stacks::stack_allocator::{AllocatorStatistics, StackAllocator};
+
+/// The next token given to a thread. 0 means that a slot is not claimed.
+static NEXT_THREAD_TOKEN: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// The token identifying the current thread, unique for the lifetime of the program.
+    static THREAD_TOKEN: usize = NEXT_THREAD_TOKEN.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A set of `StackAllocator`s, one per worker thread.
+///
+/// The first time a thread asks for its allocator, it claims an allocator which is not used by another thread.
+/// It will get the same allocator until all the allocators are reset. Since an allocator is only used by one thread,
+/// the allocations don't need any lock.
+///
+/// All the allocators are reset at once, with a mutable reference to the set, at the end of a frame for example.
+/// The reset also releases the claims: a thread may get another allocator after it.
+///
+/// The allocated data must implement the `Send` trait: it is dropped by the thread resetting the set.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::AllocatorSet;
+/// use std::sync::Arc;
+/// use std::thread;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// let set = Arc::new(AllocatorSet::with_capacity(4, 1000, 1000));
+///
+/// let workers: Vec<_> = (0..4).map(|i| {
+///     let set = set.clone();
+///     thread::spawn(move || {
+///         let allocator = set.local().unwrap();
+///         let my_string = allocator.alloc(|| format!("job {}", i)).unwrap();
+///         assert_eq!(my_string, &format!("job {}", i));
+///     })
+/// }).collect();
+///
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+///
+/// // The frame barrier: all the workers are done.
+/// let mut set = Arc::try_unwrap(set).unwrap();
+/// assert!(set.statistics().used > 0);
+///
+/// set.reset_all();
+/// assert_eq!(set.statistics().used, 0);
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+pub struct AllocatorSet {
+    allocators: Vec<StackAllocator>,
+    /// The token of the thread using the allocator with the same index, 0 if the allocator is not claimed.
+    owners: Vec<AtomicUsize>,
+}
+
+//Through a shared reference, the set only touches:
+//- the atomic owners, from any thread,
+//- the allocators, only from the thread which claimed them, through a LocalAllocator which cannot be sent to another thread.
+//The cells of an allocator (its memory chunks, its generation and its call site tracker) are never touched by another
+//thread, which is why the set is not Debug-derived: formatting it would borrow the memory chunks of all the allocators.
+unsafe impl Sync for AllocatorSet {}
+
+impl AllocatorSet {
+    /// Creates a set of `count` StackAllocators, with the given capacities in bytes.
+    ///
+    /// The first capacity is for the memory storages holding data implementing the `Drop` trait,
+    /// the second is for the memory storages holding data implementing the `Copy` trait.
+    pub fn with_capacity(count: usize, capacity: usize, capacity_copy: usize) -> Self {
+        debug!("Creating a set of {} stack allocators of {} bytes for droppable data and {} bytes for copyable data.", count, capacity, capacity_copy);
+        AllocatorSet {
+            allocators: (0..count)
+                .map(|_| StackAllocator::with_capacity(capacity, capacity_copy))
+                .collect(),
+            owners: (0..count).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    /// Returns the allocator of the current thread, claiming one if the thread doesn't have one yet.
+    ///
+    /// Returns `None` if all the allocators are used by other threads.
+    pub fn local(&self) -> Option<LocalAllocator> {
+        debug!("Getting the allocator of the current thread.");
+        let token = THREAD_TOKEN.with(|token| *token);
+
+        //There are as many allocators as worker threads, a linear search is enough.
+        trace!("Looking for the allocator claimed by the thread {}.", token);
+        if let Some(index) = self.owners.iter().position(|owner| owner.load(Ordering::Acquire) == token) {
+            return Some(self.local_at(index));
+        }
+
+        trace!("Claiming an allocator for the thread {}.", token);
+        for (index, owner) in self.owners.iter().enumerate() {
+            if owner.compare_exchange(0, token, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+                debug!("The thread {} claimed the allocator {}.", token, index);
+                return Some(self.local_at(index));
+            }
+        }
+
+        error!("All the allocators of the set are used by other threads !");
+        None
+    }
+
+    fn local_at(&self, index: usize) -> LocalAllocator {
+        LocalAllocator {
+            allocator: &self.allocators[index],
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns the number of allocators in the set.
+    pub fn len(&self) -> usize {
+        self.allocators.len()
+    }
+
+    /// Returns whether or not the set has no allocator.
+    pub fn is_empty(&self) -> bool {
+        self.allocators.is_empty()
+    }
+
+    /// Returns the number of allocators claimed by a thread.
+    pub fn claimed(&self) -> usize {
+        self.owners
+            .iter()
+            .filter(|owner| owner.load(Ordering::Relaxed) != 0)
+            .count()
+    }
+
+    /// Returns an iterator over the allocators of the set.
+    ///
+    /// A mutable reference to the set is needed: no thread can allocate while the allocators are inspected.
+    pub fn iter_mut(&mut self) -> slice::IterMut<StackAllocator> {
+        self.allocators.iter_mut()
+    }
+
+    /// Returns the memory used by all the allocators of the set.
+    pub fn statistics(&mut self) -> AllocatorStatistics {
+        debug!("Getting the memory used by the set of stack allocators.");
+        self.allocators
+            .iter()
+            .fold(AllocatorStatistics::default(), |total, allocator| total + allocator.statistics())
+    }
+
+    /// Resets both memory storages of all the allocators, dropping their content, and releases the claims of the threads.
+    ///
+    /// # Panic
+    /// If a destructor panics, the remaining objects of all the allocators are still dropped and the allocators are reset.
+    /// The panic of the first panicking destructor is then resumed.
+    pub fn reset_all(&mut self) {
+        debug!("Resetting all the allocators of the set.");
+        let mut result = Ok(());
+
+        for (allocator, owner) in self.allocators.iter().zip(self.owners.iter_mut()) {
+            allocator.reset_copy();
+            //A panicking destructor must not prevent the other allocators from being reset.
+            let reset = panic::catch_unwind(AssertUnwindSafe(|| allocator.reset()));
+            if result.is_ok() {
+                result = reset;
+            }
+            *owner.get_mut() = 0;
+        }
+
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl fmt::Debug for AllocatorSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //Only the atomic owners are read, the allocators may be used by other threads.
+        let claimed: Vec<usize> = self.owners
+            .iter()
+            .enumerate()
+            .filter(|&(_, owner)| owner.load(Ordering::Relaxed) != 0)
+            .map(|(index, _)| index)
+            .collect();
+        write!(f, "AllocatorSet [Allocators: {}, Claimed: {:?}]", self.allocators.len(), claimed)
+    }
+}
+
+/// The allocator claimed by the current thread in an `AllocatorSet`.
+///
+/// It cannot be sent to another thread. The allocated data must implement the `Send` trait.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalAllocator<'a> {
+    allocator: &'a StackAllocator,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> LocalAllocator<'a> {
+    /// Allocates data in the allocator's memory, returning a mutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc_mut()`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&'a mut T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in the allocator of the current thread, returning a mutable reference.");
+        self.allocator.alloc_mut(op)
+    }
+
+    /// Allocates data in the allocator's memory, returning an immutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc()`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&'a T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in the allocator of the current thread, returning an immutable reference.");
+        self.allocator.alloc(op)
+    }
+
+    /// Returns the index of the first unused memory address of the memory storage storing data implementing the `Drop` trait.
+    pub fn marker(&self) -> usize {
+        self.allocator.marker()
+    }
+
+    /// Returns the index of the first unused memory address of the memory storage storing data implementing the `Copy` trait.
+    pub fn marker_copy(&self) -> usize {
+        self.allocator.marker_copy()
+    }
+
+    /// Returns the memory used by the allocator.
+    pub fn statistics(&self) -> AllocatorStatistics {
+        self.allocator.statistics()
+    }
+}
+
+#[cfg(test)]
+mod allocator_set_test {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn one_allocator_per_thread() {
+        let set = Arc::new(AllocatorSet::with_capacity(3, 1000, 1000));
+        //Every worker holds its allocator until all of them claimed one.
+        let barrier = Arc::new(Barrier::new(3));
+
+        let workers: Vec<_> = (0..3)
+            .map(|i| {
+                let set = set.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    let allocator = set.local().unwrap();
+                    let first = allocator.storage_ptr();
+                    allocator.alloc(|| vec![i; 10]).unwrap();
+                    //The thread always gets the same allocator.
+                    assert_eq!(set.local().unwrap().storage_ptr(), first);
+                    barrier.wait();
+                    first as usize
+                })
+            })
+            .collect();
+
+        let mut allocators: Vec<usize> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+        allocators.sort();
+        allocators.dedup();
+        assert_eq!(allocators.len(), 3);
+
+        //All the allocators are claimed.
+        assert_eq!(set.claimed(), 3);
+        assert!(set.local().is_none());
+        assert_eq!(format!("{:?}", set), "AllocatorSet [Allocators: 3, Claimed: [0, 1, 2]]");
+
+        let mut set = Arc::try_unwrap(set).unwrap();
+        let statistics = set.statistics();
+        assert!(statistics.used > 0);
+        assert_eq!(statistics.capacity, 3000);
+        assert_eq!(set.iter_mut().filter(|allocator| allocator.marker() > 0).count(), 3);
+
+        set.reset_all();
+        assert_eq!(set.statistics().used, 0);
+        assert_eq!(set.claimed(), 0);
+        assert!(set.local().is_some());
+    }
+
+    impl<'a> LocalAllocator<'a> {
+        fn storage_ptr(&self) -> *const u8 {
+            self.allocator.storage_as_ptr()
+        }
+    }
+}
@@ -5,8 +5,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use stacks::stack_allocator::StackAllocator;
-use allocation_error::AllocationResult;
+use stacks::stack_allocator::{InPlace, Initialized, StackAllocator, StackHandle};
+use allocation_error::{AllocationError, AllocationResult, StorageKind, TryAllocError};
+use memory_chunk::Allocations;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use memory_dump::DumpFormat;
+#[cfg(feature = "allocation_tracking")]
+use allocation_tracking::AllocationReport;
 
 /// A double-buffered allocator.
 ///
@@ -20,7 +27,7 @@ use allocation_error::AllocationResult;
 /// # Example
 ///
 /// ```rust
-/// use maskerad_memory_allocators::DoubleBufferedAllocator;
+/// use maskerad_memory_allocators::{DoubleBufferedAllocator, FrameRef};
 /// # use std::error::Error;
 /// # fn try_main() -> Result<(), Box<Error>> {
 /// //100 bytes for data implementing the Drop trait, 100 bytes for data implementing the `Copy` trait.
@@ -28,15 +35,12 @@ use allocation_error::AllocationResult;
 /// let mut closed = false;
 ///
 /// while !closed {
-///     //swap the active and inactive buffers of the allocator.
-///     allocator.swap_buffers();
-///
-///     //clear the newly active buffer.
-///     allocator.reset();
+///     //swap the active and inactive buffers of the allocator, and clear the newly active buffer.
+///     allocator.begin_frame();
 ///
 ///     //allocate with the current buffer, leaving the data in the inactive buffer intact.
 ///     //You can use this data during this frame, or the next frame.
-///     let my_vec: &Vec<u8> = allocator.alloc(|| {
+///     let my_vec: FrameRef<Vec<u8>> = allocator.alloc(|| {
 ///         Vec::with_capacity(10)
 ///     })?;
 ///
@@ -55,6 +59,8 @@ use allocation_error::AllocationResult;
 pub struct DoubleBufferedAllocator {
     buffers: [StackAllocator; 2],
     current: bool,
+    /// The number of swaps of the buffers, recorded in the markers.
+    frame: usize,
 }
 
 impl DoubleBufferedAllocator {
@@ -87,6 +93,7 @@ impl DoubleBufferedAllocator {
                 StackAllocator::with_capacity(capacity, capacity_copy),
             ],
             current: false,
+            frame: 0,
         }
     }
 
@@ -98,6 +105,7 @@ impl DoubleBufferedAllocator {
     /// # Panic
     /// This function will panic if the allocation exceeds the maximum storage capacity of the active allocator.
     ///
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
     where
         F: FnOnce() -> T,
@@ -116,6 +124,7 @@ impl DoubleBufferedAllocator {
     /// It doesn't perform any check.
     ///
     /// Use if you now that the data will fit into memory and you can't afford the checks.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc_mut_unchecked<T, F>(&self, op: F) -> &mut T
         where F: FnOnce() -> T
     {
@@ -123,19 +132,57 @@ impl DoubleBufferedAllocator {
         self.active_buffer().alloc_mut_unchecked(op)
     }
 
-    /// Allocates data in the active buffer, returning an immutable reference to the allocated data.
+    /// Allocates data in the active buffer, returning a reference branded by the frame.
     ///
     /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
     /// Otherwise, it will be placed in the other memory storage.
     ///
-    /// # Panic
-    /// This function will panic if the allocation exceeds the maximum storage capacity of the active allocator.
-    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&T>
+    /// The `FrameRef` cannot outlive the frame: the buffers cannot be swapped or reset while it is alive. It can be detached
+    /// into a handle, which can still be resolved with `get()` during the next frame, until the buffer is reset.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the active allocator.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::DoubleBufferedAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let mut allocator = DoubleBufferedAllocator::with_capacity(100, 100);
+    ///
+    /// let positions = allocator.alloc(|| vec![1, 2, 3])?;
+    /// assert_eq!(positions.len(), 3);
+    /// let positions = positions.detach();
+    ///
+    /// // During the next frame, the data of the previous frame can still be read.
+    /// allocator.swap_buffers();
+    /// allocator.reset();
+    /// assert_eq!(allocator.get(positions).unwrap(), &vec![1, 2, 3]);
+    ///
+    /// // Until its buffer is reset.
+    /// allocator.swap_buffers();
+    /// allocator.reset();
+    /// assert!(allocator.get(positions).is_none());
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<FrameRef<T>>
     where
         F: FnOnce() -> T,
     {
-        debug!("Allocating data in the double buffered allocator, returning an immutable reference.");
-        self.active_buffer().alloc(op)
+        debug!("Allocating data in the double buffered allocator, returning a reference branded by the frame.");
+        let buffer = self.active_buffer();
+        let data = buffer.alloc(op)?;
+        let handle = buffer
+            .handle(data)
+            .expect("The data has just been allocated in the active buffer.");
+
+        Ok(FrameRef { data, handle })
     }
 
     /// Allocates data in the active buffer, returning an immutable reference to the allocated data.
@@ -148,6 +195,7 @@ impl DoubleBufferedAllocator {
     /// It doesn't perform any check.
     ///
     /// Use if you now that the data will fit into memory and you can't afford the checks.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc_unchecked<T, F>(&self, op: F) -> &T
         where
             F: FnOnce() -> T,
@@ -156,14 +204,82 @@ impl DoubleBufferedAllocator {
         self.active_buffer().alloc_unchecked(op)
     }
 
+    /// Allocates data in the active buffer with a fallible initializer, returning an immutable reference
+    /// to the allocated data.
+    ///
+    /// If the closure returns an error or panics, the memory reserved for the data is given back to the active buffer.
+    ///
+    /// # Error
+    /// This function will return an `AllocationError` if the allocation exceeds the maximum storage capacity of the active buffer,
+    /// or an `InitializerError` holding the error returned by the closure.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn try_alloc_with<T, E, F>(&self, op: F) -> Result<&T, TryAllocError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        debug!("Allocating data in the double buffered allocator with a fallible initializer, returning an immutable reference.");
+        self.active_buffer().try_alloc_with(op)
+    }
+
+    /// Allocates data in the active buffer with a fallible initializer, returning a mutable reference
+    /// to the allocated data.
+    ///
+    /// If the closure returns an error or panics, the memory reserved for the data is given back to the active buffer.
+    ///
+    /// # Error
+    /// This function will return an `AllocationError` if the allocation exceeds the maximum storage capacity of the active buffer,
+    /// or an `InitializerError` holding the error returned by the closure.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn try_alloc_mut_with<T, E, F>(&self, op: F) -> Result<&mut T, TryAllocError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        debug!("Allocating data in the double buffered allocator with a fallible initializer, returning a mutable reference.");
+        self.active_buffer().try_alloc_mut_with(op)
+    }
+
+    /// Reserves memory for data implementing the `Copy` trait in the active buffer, returning a mutable reference
+    /// to the uninitialized data.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the active buffer.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_uninit<T: Copy>(&self) -> AllocationResult<&mut MaybeUninit<T>> {
+        debug!("Allocating uninitialized data in the double buffered allocator.");
+        self.active_buffer().alloc_uninit()
+    }
+
+    /// Allocates data in the active buffer, initializing it in place. Returns a mutable reference to the
+    /// allocated data.
+    ///
+    /// The data is only considered initialized, and will only be dropped, once the closure confirmed the initialization.
+    ///
+    /// See `StackAllocator::alloc_with_ptr()`.
+    ///
+    /// # Error
+    /// This function will return an `AllocationError` if the allocation exceeds the maximum storage capacity of the active buffer.
+    /// It will return an `InitializerError` holding the error returned by the closure.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_with_ptr<T, E, F>(&self, op: F) -> Result<&mut T, TryAllocError<E>>
+    where
+        F: for<'s> FnOnce(InPlace<'s, T>) -> Result<Initialized<'s>, E>,
+    {
+        debug!("Allocating data initialized in place in the double buffered allocator.");
+        self.active_buffer().alloc_with_ptr(op)
+    }
+
     /// Reset the active buffer's memory storage storing data implementing the `Drop` trait, dropping all the content residing inside it.
-    pub fn reset(&self) {
+    ///
+    /// The objects are dropped in the reverse order of their allocation, the most recent first.
+    ///
+    /// A mutable reference is needed: the references to the data of the frame cannot be alive.
+    pub fn reset(&mut self) {
         debug!("Resetting completely the memory chunk storing droppable data of the double buffered allocator.");
         self.active_buffer().reset();
     }
 
     /// Reset the active buffer's memory storage storing data implementing the `Copy` trait.
-    pub fn reset_copy(&self) {
+    pub fn reset_copy(&mut self) {
         debug!("Resetting completely the memory chunk storing copyable data of the double buffered allocator.");
         self.active_buffer().reset_copy();
     }
@@ -176,35 +292,182 @@ impl DoubleBufferedAllocator {
 
     /// Reset partially the active buffer's memory storage storing data implementing the `Drop` trait, dropping all the content residing between the marker and
     /// the first unused memory address of the memory storage.
-    pub fn reset_to_marker(&self, marker: usize) {
-        debug!("Resetting partially the memory chunk storing droppable data of the double buffered allocator, from byte {} to byte {}.", marker, self.marker());
-        self.active_buffer().reset_to_marker(marker);
+    ///
+    /// # Error
+    /// This function will return an error if the marker doesn't designate a valid location in the memory storage,
+    /// if it has been created by another allocator or for the memory storage storing data implementing the `Copy` trait,
+    /// or if it has been created during another frame: it would designate a location in the other buffer,
+    /// or a location of a previous frame.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::DoubleBufferedAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let mut allocator = DoubleBufferedAllocator::with_capacity(100, 100);
+    /// let marker = allocator.marker();
+    /// allocator.alloc(|| String::from("temporary"))?;
+    ///
+    /// allocator.reset_to_marker(marker)?;
+    /// assert_eq!(allocator.marker(), marker);
+    ///
+    /// // The marker cannot be used in the other buffer.
+    /// allocator.swap_buffers();
+    /// assert!(allocator.reset_to_marker(marker).is_err());
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn reset_to_marker(&mut self, marker: BufferMarker) -> AllocationResult<()> {
+        debug!("Resetting partially the memory chunk storing droppable data of the double buffered allocator, from byte {} to byte {}.", marker.index, self.active_buffer().marker());
+        self.check_marker(marker, StorageKind::Drop)?;
+        self.active_buffer().reset_to_marker(marker.index)
     }
 
     /// Reset partially the active buffer's memory storage storing data implementing the `Copy` trait.
-    pub fn reset_to_marker_copy(&self, marker: usize) {
-        debug!("Resetting partially the memory chunk storing copyable data of the double buffered allocator, from byte {} to byte {}.", marker, self.marker_copy());
-        self.active_buffer().reset_to_marker_copy(marker)
+    ///
+    /// # Error
+    /// This function will return an error if the marker doesn't designate a valid location in the memory storage,
+    /// if it has been created by another allocator or for the memory storage storing data implementing the `Drop` trait,
+    /// or if it has been created during another frame.
+    pub fn reset_to_marker_copy(&mut self, marker: BufferMarker) -> AllocationResult<()> {
+        debug!("Resetting partially the memory chunk storing copyable data of the double buffered allocator, from byte {} to byte {}.", marker.index, self.active_buffer().marker_copy());
+        self.check_marker(marker, StorageKind::Copy)?;
+        self.active_buffer().reset_to_marker_copy(marker.index)
     }
 
-    /// Returns the index of the first unused memory address of the active buffer's memory storage storing data implementing
+    /// Returns a marker to the first unused memory address of the active buffer's memory storage storing data implementing
     /// the `Drop` trait.
-    pub fn marker(&self) -> usize {
+    pub fn marker(&self) -> BufferMarker {
         debug!("Getting a marker from the memory chunk storing droppable data of the double buffered allocator.");
-        self.active_buffer().marker()
+        self.buffer_marker(self.active_buffer().marker(), StorageKind::Drop)
     }
 
-    /// Returns the index of the first unused memory address of the active buffer's memory storage storing data implementing
+    /// Returns a marker to the first unused memory address of the active buffer's memory storage storing data implementing
     /// the `Copy` trait.
-    pub fn marker_copy(&self) -> usize {
+    pub fn marker_copy(&self) -> BufferMarker {
         debug!("Getting a marker from the memory chunk storing copyable data of the double buffered allocator.");
-        self.active_buffer().marker_copy()
+        self.buffer_marker(self.active_buffer().marker_copy(), StorageKind::Copy)
+    }
+
+    /// Tags the given index of a memory storage of the active buffer with the buffer, the memory storage and the frame.
+    fn buffer_marker(&self, index: usize, storage: StorageKind) -> BufferMarker {
+        BufferMarker {
+            allocator: self.active_buffer().id(),
+            storage,
+            frame: self.frame,
+            index,
+        }
+    }
+
+    /// Checks that the marker has been created by the given memory storage of the active buffer, during the current frame.
+    fn check_marker(&self, marker: BufferMarker, storage: StorageKind) -> AllocationResult<()> {
+        if marker.allocator == self.active_buffer().id() && marker.storage == storage && marker.frame == self.frame {
+            return Ok(());
+        }
+
+        error!("The marker has been created by another memory storage, or during another frame !");
+        let fill = match storage {
+            StorageKind::Drop => self.active_buffer().marker(),
+            StorageKind::Copy => self.active_buffer().marker_copy(),
+        };
+        Err(AllocationError::InvalidMarkerError {
+            marker: marker.index,
+            fill,
+            storage,
+        })
     }
 
     /// Swap the buffers. The inactive one becomes the active.
+    ///
+    /// The markers created before the swap cannot be used anymore.
     pub fn swap_buffers(&mut self) {
         debug!("Swapping the active buffer of the double buffered allocator with the inactive one.");
         self.current = !self.current;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Swaps the buffers and resets both memory storages of the newly active buffer, dropping the data of two frames ago.
+    ///
+    /// The data of the previous frame stays in the inactive buffer.
+    ///
+    /// # Panic
+    /// If a destructor panics during the reset, the panic is resumed once the buffers are swapped and the buffer reset.
+    pub fn begin_frame(&mut self) {
+        debug!("Beginning the frame {} of the double buffered allocator.", self.frame.wrapping_add(1));
+        self.swap_buffers();
+        //A panicking destructor must not prevent the other memory storage from being reset.
+        let active = self.active_buffer();
+        let reset_copy = panic::catch_unwind(AssertUnwindSafe(|| active.reset_copy()));
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| active.reset()));
+
+        if let Err(payload) = reset_copy.and(reset) {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Returns the number of swaps of the buffers.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Allocates a clone of the given data in the active buffer, returning an immutable reference to the clone.
+    ///
+    /// Data of the previous frame which must live longer can be carried over to the current frame, without a heap allocation.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the active allocator.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::DoubleBufferedAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let mut allocator = DoubleBufferedAllocator::with_capacity(100, 100);
+    /// let mut target = allocator.alloc(|| [4.0, 2.0])?.detach();
+    ///
+    /// for _ in 0..10 {
+    ///     allocator.begin_frame();
+    ///
+    ///     // The target of the previous frame is kept for the current frame.
+    ///     let previous_target = allocator.get(target).unwrap();
+    ///     let current_target = allocator.carry_over(previous_target)?;
+    ///     target = allocator.handle(current_target).unwrap();
+    /// }
+    ///
+    /// assert_eq!(allocator.frame(), 10);
+    /// assert_eq!(allocator.get(target), Some(&[4.0, 2.0]));
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn carry_over<T: Clone>(&self, data: &T) -> AllocationResult<&T> {
+        debug!("Carrying data over to the active buffer of the double buffered allocator.");
+        self.active_buffer().alloc(|| data.clone())
+    }
+
+    /// Creates a handle to data allocated in the active buffer.
+    ///
+    /// See `StackAllocator::handle()`.
+    pub fn handle<T>(&self, data: &T) -> Option<StackHandle<T>> {
+        self.active_buffer().handle(data)
+    }
+
+    /// Returns a reference to the data designated by the handle, whether it lies in the active or in the inactive buffer.
+    ///
+    /// Returns `None` if the handle has been created by another allocator, or if its buffer has been reset since its creation.
+    ///
+    /// See `alloc()`.
+    pub fn get<T>(&self, handle: StackHandle<T>) -> Option<&T> {
+        debug!("Resolving a handle in the buffers of the double buffered allocator.");
+        self.buffers.iter().filter_map(|buffer| buffer.get(handle)).next()
     }
 
     /// Returns the maximum capacity the memory storage storing data implementing the `Drop` trait can hold.
@@ -230,6 +493,93 @@ impl DoubleBufferedAllocator {
         debug!("Getting a raw pointer to the start of the allocation of the memory chunk storing copyable data of the double buffered allocator.");
         self.active_buffer().storage_copy_as_ptr()
     }
+
+    /// Returns an iterator over the objects living in the active buffer's memory storage storing data implementing
+    /// the `Drop` trait, from the bottom of the stack to its top.
+    ///
+    /// See `StackAllocator::allocations()`: the data allocated while iterating is not yielded.
+    pub fn allocations(&self) -> Allocations {
+        debug!("Getting an iterator over the data contained in the memory chunk storing droppable data of the double buffered allocator.");
+        self.active_buffer().allocations()
+    }
+
+    /// Renders the memory map of the active buffer in the given format.
+    pub fn dump(&self, format: DumpFormat) -> String {
+        debug!("Dumping the memory map of the active buffer of the double buffered allocator.");
+        self.active_buffer().dump(format)
+    }
+
+    /// Returns a report of the live allocations of the active buffer, aggregated by the source location
+    /// which asked for them.
+    ///
+    /// This function is only available with the `allocation_tracking` feature.
+    #[cfg(feature = "allocation_tracking")]
+    pub fn report(&self) -> AllocationReport {
+        debug!("Creating a report of the live allocations of the active buffer of the double buffered allocator, by call site.");
+        self.active_buffer().report()
+    }
+}
+
+/// A marker of a `DoubleBufferedAllocator`, created by `marker()` or `marker_copy()`.
+///
+/// It records the buffer, the memory storage and the frame in which it has been created: the allocator can only be
+/// reset to the marker in the same memory storage, during the same frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferMarker {
+    /// The identifier of the `StackAllocator` of the buffer, unique in the process.
+    allocator: usize,
+    storage: StorageKind,
+    /// The number of swaps of the buffers when the marker was created.
+    frame: usize,
+    /// The index of the first unused memory address, in the memory storage of the buffer.
+    index: usize,
+}
+
+impl BufferMarker {
+    /// Returns the index of the first unused memory address when the marker was created, in the memory storage of the buffer.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the memory storage in which the marker has been created.
+    pub fn storage(&self) -> StorageKind {
+        self.storage
+    }
+}
+
+/// A reference to data allocated in the active buffer of a `DoubleBufferedAllocator`, which cannot outlive the frame.
+///
+/// It borrows the allocator: the buffers cannot be swapped or reset while it is alive.
+///
+/// It is created by `DoubleBufferedAllocator::alloc()`, and can be detached into a `StackHandle` to read the data
+/// during the next frame.
+#[derive(Debug)]
+pub struct FrameRef<'frame, T: 'frame> {
+    data: &'frame T,
+    handle: StackHandle<T>,
+}
+
+impl<'frame, T> FrameRef<'frame, T> {
+    /// Returns a handle to the data, which can be resolved by `DoubleBufferedAllocator::get()` until the buffer is reset.
+    pub fn detach(self) -> StackHandle<T> {
+        self.handle
+    }
+}
+
+impl<'frame, T> Clone for FrameRef<'frame, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'frame, T> Copy for FrameRef<'frame, T> {}
+
+impl<'frame, T> Deref for FrameRef<'frame, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.data
+    }
 }
 
 #[cfg(test)]
@@ -260,7 +610,7 @@ mod double_buffer_allocator_test {
 
     #[test]
     fn reset() {
-        let alloc = DoubleBufferedAllocator::with_capacity(100, 100);
+        let mut alloc = DoubleBufferedAllocator::with_capacity(100, 100);
 
         let start_chunk_active_buffer = alloc.active_buffer().storage_as_ptr();
 
@@ -329,4 +679,117 @@ mod double_buffer_allocator_test {
             assert_ne!(start_chunk_second_buffer, second_buffer_top_stack);
         }
     }
+
+    #[test]
+    fn tagged_markers() {
+        let mut alloc = DoubleBufferedAllocator::with_capacity(100, 100);
+        let marker = alloc.marker();
+        let marker_copy = alloc.marker_copy();
+        alloc.alloc(|| Monster::default()).unwrap();
+        let inner_marker = alloc.marker();
+        alloc.alloc(|| Monster::default()).unwrap();
+        alloc.alloc(|| 26 as u32).unwrap();
+
+        //Nested markers of the same frame.
+        alloc.reset_to_marker(inner_marker).unwrap();
+        alloc.reset_to_marker(marker).unwrap();
+        assert_eq!(alloc.marker().index(), 0);
+
+        //The markers of the other buffer are rejected.
+        alloc.swap_buffers();
+        alloc.alloc(|| Monster::default()).unwrap();
+        assert_eq!(
+            alloc.reset_to_marker(marker),
+            Err(AllocationError::InvalidMarkerError {
+                marker: 0,
+                fill: alloc.marker().index(),
+                storage: StorageKind::Drop,
+            })
+        );
+
+        //The markers of a previous frame of the same buffer are rejected.
+        alloc.swap_buffers();
+        assert!(alloc.reset_to_marker_copy(marker_copy).is_err());
+        assert_eq!(alloc.marker_copy().index(), alloc.active_buffer().marker_copy());
+
+        //The markers of the other memory storage are rejected.
+        let marker = alloc.marker();
+        let marker_copy = alloc.marker_copy();
+        assert_eq!(marker_copy.storage(), StorageKind::Copy);
+        assert!(alloc.reset_to_marker(marker_copy).is_err());
+        assert!(alloc.reset_to_marker_copy(marker).is_err());
+
+        //The markers of another allocator are rejected, even during a frame with the same number.
+        let mut other = DoubleBufferedAllocator::with_capacity(100, 100);
+        other.swap_buffers();
+        other.swap_buffers();
+        assert_eq!(other.frame(), alloc.frame());
+        assert_eq!(other.marker().index(), marker.index());
+        assert!(other.reset_to_marker(marker).is_err());
+        alloc.reset_to_marker(marker).unwrap();
+    }
+
+    #[test]
+    fn carry_over_between_frames() {
+        let mut alloc = DoubleBufferedAllocator::with_capacity(200, 100);
+        let mut name = alloc.alloc(|| String::from("carried")).unwrap().detach();
+        let temporary = alloc.alloc(|| String::from("temporary")).unwrap().detach();
+
+        for frame in 1..5 {
+            alloc.begin_frame();
+            assert_eq!(alloc.frame(), frame);
+            assert_eq!(alloc.allocations().count(), 0);
+            assert_eq!(alloc.marker_copy().index(), 0);
+
+            let carried = alloc.carry_over(alloc.get(name).unwrap()).unwrap();
+            name = alloc.handle(carried).unwrap();
+        }
+
+        assert_eq!(alloc.get(name).unwrap(), "carried");
+        assert!(alloc.get(temporary).is_none());
+    }
+
+    #[test]
+    fn panicking_destructor_at_the_beginning_of_a_frame() {
+        struct Bomb;
+
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("The bomb exploded !");
+            }
+        }
+
+        let mut alloc = DoubleBufferedAllocator::with_capacity(100, 100);
+        alloc.alloc(|| Bomb).unwrap();
+        alloc.alloc(|| 26 as u32).unwrap();
+        alloc.begin_frame();
+
+        //The panic is resumed once both memory storages of the buffer are reset.
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| alloc.begin_frame())).is_err());
+        assert_eq!(alloc.marker().index(), 0);
+        assert_eq!(alloc.marker_copy().index(), 0);
+    }
+
+    #[test]
+    fn previous_frame_handles() {
+        let mut alloc = DoubleBufferedAllocator::with_capacity(100, 100);
+        let monster = alloc.alloc(|| Monster::default()).unwrap();
+        assert_eq!(monster._hp, 1);
+        let monster = monster.detach();
+        let hp = alloc.alloc(|| 26 as u32).unwrap().detach();
+
+        //The handles of the previous frame are resolved in the inactive buffer.
+        alloc.swap_buffers();
+        alloc.reset();
+        alloc.reset_copy();
+        assert_eq!(alloc.get(monster).unwrap()._hp, 1);
+        assert_eq!(alloc.get(hp), Some(&26));
+
+        //The next swap reuses the buffer of the handles.
+        alloc.swap_buffers();
+        assert!(alloc.get(hp).is_some());
+        alloc.reset_copy();
+        assert!(alloc.get(hp).is_none());
+        assert!(alloc.get(monster).is_none());
+    }
 }
@@ -0,0 +1,403 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+use allocation_error::AllocationResult;
+use stacks::stack_allocator::{AllocatorStatistics, StackAllocator};
+
+/// A `StackAllocator` with an explicit frame lifecycle.
+///
+/// `begin_frame()` returns a `FrameToken`, which is required to allocate. `end_frame()` consumes the token and resets
+/// the allocator: the references to the data of the frame cannot outlive it.
+///
+/// Temporary data can be allocated in a `FrameScope`, rolled back when the scope is dropped. The frame token cannot be
+/// used while a scope is open.
+///
+/// The allocator panics if a frame begins before the previous one has been ended, and if the token does not belong to
+/// the current frame: the data allocated with it could be rolled back by a scope while it is still referenced. In debug
+/// builds, it also panics if the frame ends while scopes are still open, which happens when a scope has been leaked.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::FrameAllocator;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// let mut allocator = FrameAllocator::with_capacity(100, 100);
+///
+/// for _ in 0..10 {
+///     let mut frame = allocator.begin_frame();
+///     let positions = allocator.alloc(&frame, || [0.0, 1.0, 2.0])?;
+///
+///     {
+///         // The temporary data of the scope is dropped at the end of the scope.
+///         let scope = allocator.scope(&mut frame);
+///         let velocities = scope.alloc(|| [1.0, 1.0, 1.0])?;
+///         assert_eq!(positions[2] + velocities[2], 3.0);
+///     }
+///
+///     // The data of the frame is dropped at the end of the frame.
+///     allocator.end_frame(frame);
+///     assert_eq!(allocator.statistics().used_copy, 0);
+/// }
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FrameAllocator {
+    allocator: StackAllocator,
+    /// The number of frames begun by the allocator.
+    frame: usize,
+    in_frame: bool,
+    /// The number of scopes which have not been dropped yet.
+    scopes: Cell<usize>,
+}
+
+/// The proof that a frame of a `FrameAllocator` is running, returned by `FrameAllocator::begin_frame()`.
+///
+/// It is required to allocate in the frame, and consumed by `FrameAllocator::end_frame()`.
+#[derive(Debug)]
+pub struct FrameToken {
+    allocator: usize,
+    frame: usize,
+}
+
+impl FrameAllocator {
+    /// Creates a FrameAllocator with the given capacities in bytes.
+    ///
+    /// The first capacity is for the memory storage holding data implementing the `Drop` trait,
+    /// the second is for the memory storage holding data implementing the `Copy` trait.
+    pub fn with_capacity(capacity: usize, capacity_copy: usize) -> Self {
+        debug!("Creating a frame allocator with {} bytes for droppable data and {} bytes for copyable data.", capacity, capacity_copy);
+        FrameAllocator {
+            allocator: StackAllocator::with_capacity(capacity, capacity_copy),
+            frame: 0,
+            in_frame: false,
+            scopes: Cell::new(0),
+        }
+    }
+
+    /// Begins a new frame, and returns its token.
+    ///
+    /// # Panic
+    /// This function will panic if the previous frame has not been ended.
+    pub fn begin_frame(&mut self) -> FrameToken {
+        //Only one token can be alive: a token of a previous frame could allocate in the frame while a scope is open.
+        assert!(!self.in_frame, "The frame {} of the frame allocator has not been ended.", self.frame);
+        self.frame = self.frame.wrapping_add(1);
+        self.in_frame = true;
+        debug!("Beginning the frame {} of the frame allocator.", self.frame);
+
+        FrameToken {
+            allocator: self.allocator.id(),
+            frame: self.frame,
+        }
+    }
+
+    /// Ends the frame of the token, and resets the allocator: the data of the frame is dropped.
+    ///
+    /// # Panic
+    /// This function will panic if the token does not belong to the current frame. In debug builds, it will panic if
+    /// scopes of the frame have not been dropped.
+    ///
+    /// If a destructor panics during the reset, the panic is resumed once the allocator is reset.
+    pub fn end_frame(&mut self, token: FrameToken) {
+        debug!("Ending the frame {} of the frame allocator.", token.frame);
+        self.check_token(&token);
+        debug_assert_eq!(
+            self.scopes.get(),
+            0,
+            "The frame {} of the frame allocator ends with outstanding scope guards.",
+            self.frame
+        );
+
+        self.in_frame = false;
+        self.scopes.set(0);
+        self.allocator.reset_copy();
+        self.allocator.reset();
+    }
+
+    /// Allocates data in the frame, returning an immutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc()`.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    ///
+    /// # Panic
+    /// This function will panic if the token does not belong to the current frame.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc<'a, T, F>(&'a self, token: &FrameToken, op: F) -> AllocationResult<&'a T>
+    where
+        F: FnOnce() -> T,
+    {
+        self.check_token(token);
+        self.allocator.alloc(op)
+    }
+
+    /// Allocates data in the frame, returning a mutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc_mut()`.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    ///
+    /// # Panic
+    /// This function will panic if the token does not belong to the current frame.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_mut<'a, T, F>(&'a self, token: &FrameToken, op: F) -> AllocationResult<&'a mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        self.check_token(token);
+        self.allocator.alloc_mut(op)
+    }
+
+    /// Opens a scope in the frame. The data allocated in the scope is dropped when the scope is dropped.
+    ///
+    /// The token is borrowed by the scope: nothing can be allocated in the frame while the scope is open.
+    ///
+    /// # Panic
+    /// This function will panic if the token does not belong to the current frame.
+    pub fn scope<'a>(&'a self, token: &'a mut FrameToken) -> FrameScope<'a> {
+        self.check_token(token);
+        FrameScope::new(self, token)
+    }
+
+    /// Returns the number of frames begun by the allocator.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Returns the memory used by the allocator.
+    pub fn statistics(&self) -> AllocatorStatistics {
+        self.allocator.statistics()
+    }
+
+    /// Checks that the token belongs to the current frame of the allocator.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn check_token(&self, token: &FrameToken) {
+        //The token of another allocator, or of another frame, would allow allocations in the frame while a scope is open:
+        //the scope would roll them back while they are still referenced.
+        assert!(
+            token.allocator == self.allocator.id(),
+            "The token of the frame {} is used by another frame allocator.",
+            token.frame
+        );
+        assert!(
+            self.in_frame && token.frame == self.frame,
+            "The token of the frame {} is used outside of its frame.",
+            token.frame
+        );
+    }
+}
+
+/// A scope in a frame of a `FrameAllocator`, created by `FrameAllocator::scope()`.
+///
+/// The data allocated in the scope is dropped when the scope is dropped. Scopes can be nested.
+///
+/// If a destructor panics when the scope is dropped, the panic is resumed once both memory storages are rolled back.
+/// It is discarded if the scope is dropped during a panic.
+#[derive(Debug)]
+pub struct FrameScope<'a> {
+    allocator: &'a FrameAllocator,
+    /// The token is borrowed to forbid allocations in the frame, or in the enclosing scope, while the scope is open.
+    token: &'a mut FrameToken,
+    marker: usize,
+    marker_copy: usize,
+}
+
+impl<'a> FrameScope<'a> {
+    fn new(allocator: &'a FrameAllocator, token: &'a mut FrameToken) -> Self {
+        debug!("Opening a scope in the frame {} of the frame allocator.", token.frame);
+        allocator.scopes.set(allocator.scopes.get() + 1);
+        FrameScope {
+            allocator,
+            token,
+            marker: allocator.allocator.marker(),
+            marker_copy: allocator.allocator.marker_copy(),
+        }
+    }
+
+    /// Allocates data in the scope, returning an immutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc()`.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&T>
+    where
+        F: FnOnce() -> T,
+    {
+        self.allocator.allocator.alloc(op)
+    }
+
+    /// Allocates data in the scope, returning a mutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc_mut()`.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        self.allocator.allocator.alloc_mut(op)
+    }
+
+    /// Opens a scope nested in this scope. Nothing can be allocated in this scope while the nested scope is open.
+    pub fn scope(&mut self) -> FrameScope {
+        FrameScope::new(self.allocator, self.token)
+    }
+}
+
+impl<'a> Drop for FrameScope<'a> {
+    fn drop(&mut self) {
+        debug!("Closing a scope of the frame allocator, rolling back to the markers {} and {}.", self.marker, self.marker_copy);
+        self.allocator.scopes.set(self.allocator.scopes.get() - 1);
+        //The allocator cannot be reset while the scope is open: the markers are always valid.
+        //A panicking destructor must not prevent the other memory storage from being rolled back.
+        let allocator = &self.allocator.allocator;
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = allocator.reset_to_marker(self.marker);
+        }));
+        let reset_copy = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = allocator.reset_to_marker_copy(self.marker_copy);
+        }));
+
+        if let Err(payload) = reset.and(reset_copy) {
+            //Resuming a panic while the thread is already unwinding would abort the process.
+            if thread::panicking() {
+                error!("A destructor panicked while a scope of the frame allocator was dropped during a panic, the panic is discarded !");
+            } else {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod frame_allocator_test {
+    use super::*;
+
+    #[test]
+    fn frame_lifecycle() {
+        let mut allocator = FrameAllocator::with_capacity(1000, 1000);
+
+        for frame in 1..4 {
+            let mut token = allocator.begin_frame();
+            assert_eq!(allocator.frame(), frame);
+
+            let name = allocator.alloc(&token, || format!("frame {}", frame)).unwrap();
+            let used = allocator.statistics();
+            {
+                let mut scope = allocator.scope(&mut token);
+                scope.alloc(|| String::from("temporary")).unwrap();
+                {
+                    let nested = scope.scope();
+                    nested.alloc_mut(|| 5 as u64).unwrap();
+                }
+                scope.alloc(|| 4 as u32).unwrap();
+            }
+
+            //The scopes have been rolled back, the data of the frame is still alive.
+            assert_eq!(allocator.statistics(), used);
+            assert_eq!(name, &format!("frame {}", frame));
+
+            allocator.end_frame(token);
+            assert_eq!(allocator.statistics().used, 0);
+        }
+    }
+
+    #[test]
+    fn panicking_destructor_in_a_scope() {
+        struct Bomb;
+
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("The bomb exploded !");
+            }
+        }
+
+        let mut allocator = FrameAllocator::with_capacity(1000, 1000);
+        let mut token = allocator.begin_frame();
+        let used = allocator.statistics();
+
+        //The panic of the destructor is discarded, the panic of the scope is resumed.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let scope = allocator.scope(&mut token);
+            scope.alloc(|| Bomb).unwrap();
+            scope.alloc(|| 26 as u64).unwrap();
+            panic!("The scope failed !");
+        }));
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"The scope failed !"));
+        assert_eq!(allocator.statistics(), used);
+
+        //Without another panic, the panic of the destructor is resumed once both memory storages are rolled back.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let scope = allocator.scope(&mut token);
+            scope.alloc(|| Bomb).unwrap();
+            scope.alloc(|| 26 as u64).unwrap();
+        }));
+        assert!(result.is_err());
+        assert_eq!(allocator.statistics(), used);
+
+        allocator.end_frame(token);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outstanding scope guards")]
+    fn leaked_scope() {
+        let mut allocator = FrameAllocator::with_capacity(1000, 1000);
+        let mut token = allocator.begin_frame();
+        std::mem::forget(allocator.scope(&mut token));
+        allocator.end_frame(token);
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been ended")]
+    fn stale_token() {
+        let mut allocator = FrameAllocator::with_capacity(1000, 1000);
+        let stale = allocator.begin_frame();
+        let mut token = allocator.begin_frame();
+        let scope = allocator.scope(&mut token);
+        allocator.alloc(&stale, || String::from("rolled back by the scope")).unwrap();
+        drop(scope);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of its frame")]
+    fn token_of_an_ended_frame() {
+        let mut allocator = FrameAllocator::with_capacity(1000, 1000);
+        let token = allocator.begin_frame();
+        let frame = token.frame;
+        allocator.end_frame(token);
+
+        //The token of an ended frame cannot be used, even if it is forged from its fields.
+        let stale = FrameToken { allocator: allocator.allocator.id(), frame };
+        allocator.alloc(&stale, || 3 as u8).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "another frame allocator")]
+    fn token_of_another_allocator() {
+        let mut allocator = FrameAllocator::with_capacity(1000, 1000);
+        let mut other = FrameAllocator::with_capacity(1000, 1000);
+        let _token = allocator.begin_frame();
+        let other_token = other.begin_frame();
+        allocator.alloc(&other_token, || 3 as u8).unwrap();
+    }
+}
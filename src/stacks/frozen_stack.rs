@@ -0,0 +1,139 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+use stacks::stack_allocator::{AllocatorStatistics, StackAllocator, StackHandle};
+
+/// A read-only view of a `StackAllocator`, created by `StackAllocator::freeze()`.
+///
+/// Nothing can be allocated in a frozen allocator, and it cannot be reset: the view can be shared between threads.
+/// The data is read with the handles created before the allocator was frozen. Only data implementing the `Sync`
+/// trait can be read.
+///
+/// The allocator is given back by `thaw()`, once all the readers are done.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::StackAllocator;
+/// use std::sync::Arc;
+/// use std::thread;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// let allocator = StackAllocator::with_capacity(100, 100);
+///
+/// // Build the render data of the frame.
+/// let vertices = {
+///     let vertices = allocator.alloc(|| vec![0.0, 1.0, 2.0])?;
+///     allocator.handle(vertices).unwrap()
+/// };
+///
+/// // The render thread reads the data of the frame.
+/// let frozen = Arc::new(allocator.freeze());
+/// let render_thread = {
+///     let frozen = frozen.clone();
+///     thread::spawn(move || frozen.get(vertices).unwrap().len())
+/// };
+/// assert_eq!(render_thread.join().unwrap(), 3);
+///
+/// // All the readers are done, the allocator can be used for the next frame.
+/// let allocator = Arc::try_unwrap(frozen).unwrap().thaw();
+/// allocator.reset();
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+pub struct FrozenStack {
+    allocator: StackAllocator,
+}
+
+//The view never borrows the memory chunks of the allocator, and never writes in them.
+//The data it gives access to implements the Sync trait.
+unsafe impl Sync for FrozenStack {}
+
+impl FrozenStack {
+    pub(crate) fn new(allocator: StackAllocator) -> Self {
+        FrozenStack { allocator }
+    }
+
+    /// Returns a reference to the data designated by the handle.
+    ///
+    /// Returns `None` if the handle has been created by another allocator, or if the allocator has been reset since its creation.
+    pub fn get<T: Sync>(&self, handle: StackHandle<T>) -> Option<&T> {
+        debug!("Resolving a handle to data allocated by a frozen stack allocator.");
+        self.allocator.resolve(handle).map(|data| unsafe { &*data })
+    }
+
+    /// Returns the memory used by the allocator.
+    pub fn statistics(&self) -> AllocatorStatistics {
+        self.allocator.statistics()
+    }
+
+    /// Gives the allocator back, to allocate data or reset it again.
+    pub fn thaw(self) -> StackAllocator {
+        debug!("Thawing a frozen stack allocator.");
+        self.allocator
+    }
+}
+
+impl fmt::Debug for FrozenStack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //The allocator cannot be formatted, it would borrow its memory chunks.
+        let statistics = self.statistics();
+        write!(
+            f,
+            "FrozenStack [Droppable data: {} / {} bytes, Copyable data: {} / {} bytes]",
+            statistics.used, statistics.capacity, statistics.used_copy, statistics.capacity_copy
+        )
+    }
+}
+
+#[cfg(test)]
+mod frozen_stack_test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn shared_between_threads() {
+        let allocator = StackAllocator::with_capacity(1000, 1000);
+        let names = allocator.alloc(|| vec![String::from("first"), String::from("second")]).unwrap();
+        let names = allocator.handle(names).unwrap();
+        let count = allocator.handle(allocator.alloc(|| 2 as u64).unwrap()).unwrap();
+
+        //A handle cannot be created for data which doesn't live in the allocator.
+        assert!(allocator.handle(&0).is_none());
+
+        let frozen = Arc::new(allocator.freeze());
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = frozen.clone();
+                thread::spawn(move || {
+                    assert_eq!(frozen.get(names).unwrap()[1], "second");
+                    *frozen.get(count).unwrap()
+                })
+            })
+            .collect();
+
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 2);
+        }
+
+        //The handles of another allocator cannot be resolved.
+        let other = StackAllocator::with_capacity(1000, 1000);
+        assert!(other.get(count).is_none());
+
+        let allocator = Arc::try_unwrap(frozen).unwrap().thaw();
+        assert_eq!(allocator.get(count), Some(&2));
+        allocator.reset_copy();
+        assert!(allocator.get(count).is_none());
+        assert!(allocator.get(names).is_none());
+    }
+}
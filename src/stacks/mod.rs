@@ -5,5 +5,12 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+pub mod allocator_set;
 pub mod double_buffered_allocator;
+pub mod frame_allocator;
+pub mod frozen_stack;
+pub mod multi_buffered_allocator;
+pub mod scratch;
+pub mod shared_double_buffer;
 pub mod stack_allocator;
+pub mod sync_stack_allocator;
@@ -0,0 +1,150 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::array;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+
+use stacks::stack_allocator::{StackAllocator, StackHandle};
+
+/// A ring of `N` buffers, each buffer being a `StackAllocator`.
+///
+/// The data is allocated in the active buffer. Every frame, `advance()` makes the next buffer active and resets it:
+/// the data allocated during a frame survives the `N - 1` following frames. The data of the previous frames can be read
+/// with `buffer()`, or with handles.
+///
+/// The allocator can be used as its active `StackAllocator`.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::MultiBufferedAllocator;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// // The GPU reads the data of a frame during the three following frames.
+/// let mut allocator: MultiBufferedAllocator<3> = MultiBufferedAllocator::with_capacity(100, 100);
+///
+/// let uniforms = {
+///     let uniforms = allocator.alloc(|| vec![1.0, 2.0, 3.0])?;
+///     allocator.handle(uniforms).unwrap()
+/// };
+///
+/// allocator.advance();
+/// allocator.advance();
+///
+/// // The data of two frames ago is still alive.
+/// assert_eq!(allocator.get(uniforms).unwrap()[0], 1.0);
+/// assert!(allocator.buffer(2).unwrap().marker() > 0);
+///
+/// // Its buffer is reused now.
+/// allocator.advance();
+/// assert!(allocator.get(uniforms).is_none());
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MultiBufferedAllocator<const N: usize> {
+    buffers: [StackAllocator; N],
+    /// The index of the active buffer.
+    current: usize,
+}
+
+impl<const N: usize> MultiBufferedAllocator<N> {
+    /// Creates a MultiBufferedAllocator of `N` buffers with the given capacities in bytes.
+    ///
+    /// The first capacity is for the memory storages holding data implementing the `Drop` trait,
+    /// the second is for the memory storages holding data implementing the `Copy` trait.
+    ///
+    /// # Panic
+    /// This function will panic if `N` is 0.
+    pub fn with_capacity(capacity: usize, capacity_copy: usize) -> Self {
+        debug!("Creating a multi buffered allocator of {} buffers, with {} bytes for droppable data and {} bytes for copyable data.", N, capacity, capacity_copy);
+        assert!(N > 0, "A multi buffered allocator needs at least one buffer.");
+        MultiBufferedAllocator {
+            buffers: array::from_fn(|_| StackAllocator::with_capacity(capacity, capacity_copy)),
+            current: 0,
+        }
+    }
+
+    /// Makes the next buffer active, and resets it: the data allocated `N` frames ago is dropped.
+    ///
+    /// # Panic
+    /// If a destructor panics during the reset, the panic is resumed once the buffer is reset.
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % N;
+        debug!("Advancing the multi buffered allocator to the buffer {}.", self.current);
+        let active = &self.buffers[self.current];
+        //A panicking destructor must not prevent the other memory storage from being reset.
+        let reset_copy = panic::catch_unwind(AssertUnwindSafe(|| active.reset_copy()));
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| active.reset()));
+
+        if let Err(payload) = reset_copy.and(reset) {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Returns the buffer which was active `frames_ago` frames ago. The active buffer is returned for 0.
+    ///
+    /// Returns `None` if `frames_ago` is not lower than `N`: the buffer of this frame has been reset since.
+    pub fn buffer(&self, frames_ago: usize) -> Option<&StackAllocator> {
+        debug!("Getting the buffer of the multi buffered allocator which was active {} frames ago.", frames_ago);
+        if frames_ago >= N {
+            return None;
+        }
+
+        Some(&self.buffers[(self.current + N - frames_ago) % N])
+    }
+
+    /// Returns a reference to the data designated by the handle, whichever buffer it was allocated in.
+    ///
+    /// Returns `None` if the handle has been created by another allocator, or if its buffer has been reset since its creation.
+    pub fn get<T>(&self, handle: StackHandle<T>) -> Option<&T> {
+        debug!("Resolving a handle in the buffers of the multi buffered allocator.");
+        self.buffers.iter().filter_map(|buffer| buffer.get(handle)).next()
+    }
+}
+
+impl<const N: usize> Deref for MultiBufferedAllocator<N> {
+    type Target = StackAllocator;
+
+    fn deref(&self) -> &StackAllocator {
+        &self.buffers[self.current]
+    }
+}
+
+#[cfg(test)]
+mod multi_buffered_allocator_test {
+    use super::*;
+
+    #[test]
+    fn ring_of_buffers() {
+        let mut allocator: MultiBufferedAllocator<3> = MultiBufferedAllocator::with_capacity(1000, 1000);
+        let handles: Vec<_> = (0..3)
+            .map(|frame| {
+                let data = allocator.alloc(|| format!("frame {}", frame)).unwrap();
+                let handle = allocator.handle(data).unwrap();
+                allocator.advance();
+                handle
+            })
+            .collect();
+
+        //The ring came back to the first buffer, its data has been dropped.
+        assert_eq!(allocator.marker(), 0);
+        assert!(allocator.get(handles[0]).is_none());
+        assert_eq!(allocator.get(handles[1]).unwrap(), "frame 1");
+        assert_eq!(allocator.get(handles[2]).unwrap(), "frame 2");
+
+        //The buffers of the previous frames.
+        assert!(allocator.buffer(0).unwrap().get(handles[0]).is_none());
+        assert!(allocator.buffer(1).unwrap().get(handles[2]).is_some());
+        assert!(allocator.buffer(2).unwrap().get(handles[1]).is_some());
+        assert!(allocator.buffer(3).is_none());
+    }
+}
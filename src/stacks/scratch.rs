@@ -0,0 +1,243 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::thread;
+
+use stacks::stack_allocator::StackAllocator;
+
+/// The capacity, in bytes, of both memory storages of every scratch arena.
+pub const SCRATCH_CAPACITY: usize = 256 * 1024;
+
+/// The number of scratch arenas of a thread.
+///
+/// Two arenas are enough: a function writing its result in one of them can always use the other one for its temporaries.
+const SCRATCH_ARENA_COUNT: usize = 2;
+
+thread_local! {
+    static SCRATCH_ARENAS: [StackAllocator; SCRATCH_ARENA_COUNT] = [
+        StackAllocator::with_capacity(SCRATCH_CAPACITY, SCRATCH_CAPACITY),
+        StackAllocator::with_capacity(SCRATCH_CAPACITY, SCRATCH_CAPACITY),
+    ];
+}
+
+/// Gives a scratch arena of the current thread to the closure, for temporary allocations.
+///
+/// The scratch arenas are `StackAllocator`s created the first time a thread uses them, with a capacity of
+/// `SCRATCH_CAPACITY` bytes for each memory storage. When the closure returns, or panics, the arena is reset to the
+/// markers it had before the closure was called: everything allocated by the closure is dropped.
+///
+/// See `with_scratch_avoiding()` to get an arena which is not already used by the caller.
+///
+/// # Panic
+/// If a destructor panics during the rollback, the panic is resumed once both memory storages are rolled back. It is
+/// discarded if the closure panicked.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::with_scratch;
+///
+/// let sum = with_scratch(|scratch| {
+///     let numbers = scratch.alloc(|| [1, 2, 3, 4]).unwrap();
+///     numbers.iter().sum::<i32>()
+/// });
+///
+/// assert_eq!(sum, 10);
+/// ```
+pub fn with_scratch<R, F>(op: F) -> R
+where
+    F: FnOnce(&StackAllocator) -> R,
+{
+    with_scratch_avoiding(&[], op)
+}
+
+/// Gives a scratch arena of the current thread to the closure, for temporary allocations. The arena is not one of
+/// the given allocators.
+///
+/// A function writing its result in a scratch arena it received can use this function to get another scratch arena
+/// for its temporaries: rolling back the temporaries will not drop its result.
+///
+/// See `with_scratch()`.
+///
+/// # Panic
+/// This function will panic if all the scratch arenas of the current thread are in the given allocators.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::{with_scratch, with_scratch_avoiding, StackAllocator};
+///
+/// // Writes the squares of the numbers in the output arena, using another arena for its temporaries.
+/// fn squares<'a>(output: &'a StackAllocator, numbers: &[u64]) -> &'a [u64; 4] {
+///     with_scratch_avoiding(&[output], |scratch| {
+///         let temporary = scratch.alloc(|| {
+///             let mut squares = [0; 4];
+///             for (square, number) in squares.iter_mut().zip(numbers.iter()) {
+///                 *square = number * number;
+///             }
+///             squares
+///         }).unwrap();
+///
+///         output.alloc(|| *temporary).unwrap()
+///     })
+/// }
+///
+/// with_scratch(|output| {
+///     let result = squares(output, &[1, 2, 3, 4]);
+///     assert_eq!(result, &[1, 4, 9, 16]);
+/// });
+/// ```
+pub fn with_scratch_avoiding<R, F>(conflicts: &[&StackAllocator], op: F) -> R
+where
+    F: FnOnce(&StackAllocator) -> R,
+{
+    debug!("Getting a scratch arena, avoiding {} allocators.", conflicts.len());
+    SCRATCH_ARENAS.with(|arenas| {
+        let arena = arenas
+            .iter()
+            .find(|arena| !conflicts.iter().any(|conflict| ptr::eq(*conflict, *arena)))
+            .expect("All the scratch arenas of the thread are already in use.");
+
+        //Roll back the arena when the closure returns or panics.
+        let _guard = ScratchGuard {
+            arena,
+            marker: arena.marker(),
+            marker_copy: arena.marker_copy(),
+        };
+
+        op(arena)
+    })
+}
+
+/// Resets a scratch arena to the markers it had when it was given to a closure.
+struct ScratchGuard<'a> {
+    arena: &'a StackAllocator,
+    marker: usize,
+    marker_copy: usize,
+}
+
+impl<'a> Drop for ScratchGuard<'a> {
+    fn drop(&mut self) {
+        debug!("Rolling back the scratch arena to the markers {} and {}.", self.marker, self.marker_copy);
+        //The markers are invalid only if the closure reset the arena below them: there is nothing left to roll back.
+        //A panicking destructor must not prevent the other memory storage from being rolled back.
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = self.arena.reset_to_marker(self.marker);
+        }));
+        let reset_copy = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _ = self.arena.reset_to_marker_copy(self.marker_copy);
+        }));
+
+        if let Err(payload) = reset.and(reset_copy) {
+            //Resuming a panic while the closure is already unwinding would abort the process.
+            if thread::panicking() {
+                error!("A destructor panicked while the scratch arena was rolled back during a panic, the panic is discarded !");
+            } else {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod scratch_test {
+    use super::*;
+
+    struct Bomb;
+
+    impl Drop for Bomb {
+        fn drop(&mut self) {
+            panic!("The bomb exploded !");
+        }
+    }
+
+    #[test]
+    fn nested_scratch_arenas() {
+        with_scratch(|output| {
+            let marker = output.marker();
+            let result = output.alloc(|| String::from("result")).unwrap();
+
+            with_scratch_avoiding(&[output], |scratch| {
+                //The arenas never interfere.
+                assert!(!ptr::eq(scratch, output));
+                scratch.alloc(|| String::from("temporary")).unwrap();
+            });
+
+            //A nested arena without conflicts rolls back to its own markers.
+            with_scratch(|nested| {
+                assert!(ptr::eq(nested, output));
+                nested.alloc(|| String::from("nested")).unwrap();
+            });
+
+            assert_eq!(result, "result");
+            assert!(output.marker() > marker);
+        });
+
+        //Everything has been rolled back.
+        with_scratch(|first| {
+            assert_eq!(first.marker(), 0);
+            with_scratch_avoiding(&[first], |second| assert_eq!(second.marker(), 0));
+        });
+    }
+
+    #[test]
+    fn rollback_on_panic() {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_scratch(|scratch| {
+                scratch.alloc(|| 26 as u64).unwrap();
+                panic!("scratch computation failed");
+            })
+        }));
+        assert!(result.is_err());
+        with_scratch(|scratch| assert_eq!(scratch.marker_copy(), 0));
+    }
+
+    #[test]
+    fn panicking_destructor_during_a_panic() {
+        //The panic of the destructor is discarded, the panic of the closure is resumed.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_scratch(|scratch| {
+                scratch.alloc(|| Bomb).unwrap();
+                scratch.alloc(|| 26 as u64).unwrap();
+                panic!("scratch computation failed");
+            })
+        }));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"scratch computation failed"));
+
+        //Both memory storages have been rolled back.
+        with_scratch(|scratch| {
+            assert_eq!(scratch.marker(), 0);
+            assert_eq!(scratch.marker_copy(), 0);
+        });
+
+        //Without another panic, the panic of the destructor is resumed once the arena is rolled back.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_scratch(|scratch| {
+                scratch.alloc(|| Bomb).unwrap();
+                scratch.alloc(|| 26 as u64).unwrap();
+            })
+        }));
+        assert!(result.is_err());
+        with_scratch(|scratch| {
+            assert_eq!(scratch.marker(), 0);
+            assert_eq!(scratch.marker_copy(), 0);
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn all_arenas_in_use() {
+        with_scratch(|first| {
+            with_scratch_avoiding(&[first], |second| {
+                with_scratch_avoiding(&[first, second], |_| {});
+            });
+        });
+    }
+}
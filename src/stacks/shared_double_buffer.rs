@@ -0,0 +1,307 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::mem;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use allocation_error::AllocationResult;
+use stacks::frozen_stack::FrozenStack;
+use stacks::stack_allocator::{AllocatorStatistics, StackAllocator, StackHandle};
+
+/// Creates a double-buffered allocator shared between a writing thread and reading threads.
+///
+/// The writer allocates in the active buffer, like a `StackAllocator`. The readers only see the inactive buffer, frozen:
+/// it cannot be modified while they read it. Swapping the buffers publishes the active buffer to the readers, and waits
+/// until they are done with the previous one.
+///
+/// Both buffers have the given capacities in bytes. The first capacity is for the memory storages holding data
+/// implementing the `Drop` trait, the second is for the memory storages holding data implementing the `Copy` trait.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::double_buffered_channel;
+/// use std::sync::mpsc;
+/// use std::thread;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// let (mut game, render) = double_buffered_channel(1000, 1000);
+/// let (sender, receiver) = mpsc::channel();
+///
+/// let render_thread = thread::spawn(move || {
+///     for vertices in receiver {
+///         // The frame is released at the end of the iteration.
+///         let frame = render.read();
+///         if let Some(vertices) = frame.get::<Vec<f32>>(vertices) {
+///             assert_eq!(vertices.len(), 3);
+///         }
+///     }
+/// });
+///
+/// for _ in 0..10 {
+///     let vertices = game.alloc(|| vec![0.0, 1.0, 2.0])?;
+///     let vertices = game.handle(vertices).unwrap();
+///
+///     // Waits for the render thread to release the previous frame.
+///     game.swap_buffers();
+///     sender.send(vertices)?;
+/// }
+///
+/// drop(sender);
+/// render_thread.join().unwrap();
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+pub fn double_buffered_channel(capacity: usize, capacity_copy: usize) -> (DoubleBufferWriter, DoubleBufferReader) {
+    debug!("Creating a double-buffered allocator shared between threads, with {} bytes for droppable data and {} bytes for copyable data.", capacity, capacity_copy);
+    let shared = Arc::new(SharedBuffer {
+        published: Mutex::new(Some(Arc::new(StackAllocator::with_capacity(capacity, capacity_copy).freeze()))),
+        released: Condvar::new(),
+    });
+
+    let writer = DoubleBufferWriter {
+        active: StackAllocator::with_capacity(capacity, capacity_copy),
+        shared: shared.clone(),
+    };
+
+    (writer, DoubleBufferReader { shared })
+}
+
+/// The buffer read by the readers, and the synchronization point of the swaps.
+#[derive(Debug)]
+struct SharedBuffer {
+    /// The inactive buffer. It is only taken by the writer during a swap.
+    published: Mutex<Option<Arc<FrozenStack>>>,
+    /// Notified when a reader releases the inactive buffer.
+    released: Condvar,
+}
+
+impl SharedBuffer {
+    /// Locks the inactive buffer.
+    ///
+    /// No code can panic while the lock is held, but a reader may be dropped during a panic.
+    fn lock(&self) -> MutexGuard<Option<Arc<FrozenStack>>> {
+        self.published.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns whether or not a reader holds the inactive buffer. The swaps wait until it is released.
+    fn is_read(published: &Option<Arc<FrozenStack>>) -> bool {
+        published.as_ref().is_some_and(|buffer| Arc::strong_count(buffer) > 1)
+    }
+}
+
+/// The writing side of a double-buffered allocator shared between threads, created by `double_buffered_channel()`.
+///
+/// It allocates in the active buffer, a `StackAllocator`. The allocated data must implement the `Send` trait: once
+/// published, it may be dropped by a reading thread.
+#[derive(Debug)]
+pub struct DoubleBufferWriter {
+    active: StackAllocator,
+    shared: Arc<SharedBuffer>,
+}
+
+impl DoubleBufferWriter {
+    /// Publishes the active buffer to the readers, and makes the inactive buffer active.
+    ///
+    /// This function waits until no reader holds the inactive buffer. The newly active buffer is then reset:
+    /// its data is dropped.
+    ///
+    /// # Panic
+    /// If a destructor panics during the reset, the panic is resumed once the buffers are swapped and the buffer reset.
+    ///
+    /// # Deadlock
+    /// This function never returns if a `ReadBuffer` is held by the thread calling it: drop it before swapping the buffers.
+    pub fn swap_buffers(&mut self) {
+        debug!("Swapping the buffers of the shared double-buffered allocator.");
+        {
+            let mut published = self.shared.lock();
+
+            trace!("Waiting for the readers to release the inactive buffer.");
+            while SharedBuffer::is_read(&published) {
+                published = self.shared.released.wait(published).unwrap_or_else(PoisonError::into_inner);
+            }
+
+            let released = published.take().expect("The inactive buffer is only taken during a swap.");
+            let released = match Arc::try_unwrap(released) {
+                Ok(released) => released.thaw(),
+                Err(_) => unreachable!("No reader holds the inactive buffer."),
+            };
+
+            trace!("Publishing the active buffer.");
+            let frame = mem::replace(&mut self.active, released);
+            *published = Some(Arc::new(frame.freeze()));
+        }
+
+        //The readers cannot see the active buffer, it can be reset without holding the lock.
+        //A panicking destructor must not prevent the other memory storage from being reset.
+        let reset_copy = panic::catch_unwind(AssertUnwindSafe(|| self.active.reset_copy()));
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| self.active.reset()));
+
+        if let Err(payload) = reset_copy.and(reset) {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Allocates data in the active buffer, returning a mutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc_mut()`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in the active buffer of the shared double-buffered allocator, returning a mutable reference.");
+        self.active.alloc_mut(op)
+    }
+
+    /// Allocates data in the active buffer, returning an immutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc()`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in the active buffer of the shared double-buffered allocator, returning an immutable reference.");
+        self.active.alloc(op)
+    }
+
+    /// Creates a handle to data allocated in the active buffer, which can be resolved by the readers once the buffer
+    /// is published.
+    ///
+    /// See `StackAllocator::handle()`.
+    pub fn handle<T: Send>(&self, data: &T) -> Option<StackHandle<T>> {
+        self.active.handle(data)
+    }
+
+    /// Returns the index of the first unused memory address of the active buffer's memory storage storing data
+    /// implementing the `Drop` trait.
+    pub fn marker(&self) -> usize {
+        self.active.marker()
+    }
+
+    /// Returns the index of the first unused memory address of the active buffer's memory storage storing data
+    /// implementing the `Copy` trait.
+    pub fn marker_copy(&self) -> usize {
+        self.active.marker_copy()
+    }
+
+    /// Returns the memory used by the active buffer.
+    pub fn statistics(&self) -> AllocatorStatistics {
+        self.active.statistics()
+    }
+}
+
+/// The reading side of a double-buffered allocator shared between threads, created by `double_buffered_channel()`.
+///
+/// It can be cloned to read the buffers from several threads.
+#[derive(Debug, Clone)]
+pub struct DoubleBufferReader {
+    shared: Arc<SharedBuffer>,
+}
+
+impl DoubleBufferReader {
+    /// Returns the buffer published by the last swap.
+    ///
+    /// The writer cannot swap the buffers again until the returned buffer is dropped.
+    pub fn read(&self) -> ReadBuffer {
+        debug!("Reading the inactive buffer of the shared double-buffered allocator.");
+        let buffer = self.shared
+            .lock()
+            .clone()
+            .expect("The inactive buffer is only taken during a swap.");
+
+        ReadBuffer {
+            buffer: Some(buffer),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// The inactive buffer of a double-buffered allocator shared between threads, frozen while it is read.
+///
+/// The data is read with the handles created by the writer, see `FrozenStack`. Dropping the buffer releases it:
+/// the writer can swap the buffers again.
+#[derive(Debug)]
+pub struct ReadBuffer {
+    buffer: Option<Arc<FrozenStack>>,
+    shared: Arc<SharedBuffer>,
+}
+
+impl Deref for ReadBuffer {
+    type Target = FrozenStack;
+
+    fn deref(&self) -> &FrozenStack {
+        self.buffer.as_ref().expect("The buffer is only released when dropped.")
+    }
+}
+
+impl Drop for ReadBuffer {
+    fn drop(&mut self) {
+        debug!("Releasing the inactive buffer of the shared double-buffered allocator.");
+        //The buffer must be released while the lock is held, the writer checks the readers with the lock.
+        let _published = self.shared.lock();
+        self.buffer.take();
+        self.shared.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod shared_double_buffer_test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn swap_waits_for_the_reader() {
+        let (mut writer, reader) = double_buffered_channel(1000, 1000);
+        let first = writer.handle(writer.alloc(|| String::from("first frame")).unwrap()).unwrap();
+
+        //Nothing has been published yet.
+        assert!(reader.read().get(first).is_none());
+
+        writer.swap_buffers();
+        assert_eq!(writer.marker(), 0);
+
+        //The swaps wait while a reader holds the inactive buffer, and only while it holds it.
+        let frame = reader.read();
+        assert!(SharedBuffer::is_read(&writer.shared.lock()));
+        let another_frame = reader.read();
+        drop(another_frame);
+        assert!(SharedBuffer::is_read(&writer.shared.lock()));
+
+        //The flag is set before the buffer is released: the swap cannot return before it is set.
+        let released = Arc::new(AtomicBool::new(false));
+        let render_thread = {
+            let released = released.clone();
+            thread::spawn(move || {
+                assert_eq!(frame.get(first).unwrap(), "first frame");
+                released.store(true, Ordering::SeqCst);
+                drop(frame);
+            })
+        };
+
+        let second = writer.handle(writer.alloc(|| String::from("second frame")).unwrap()).unwrap();
+        writer.swap_buffers();
+        assert!(released.load(Ordering::SeqCst));
+        assert!(!SharedBuffer::is_read(&writer.shared.lock()));
+        render_thread.join().unwrap();
+
+        //The readers see the second frame, the first one has been reset.
+        let frame = reader.clone().read();
+        assert_eq!(frame.get(second).unwrap(), "second frame");
+        assert!(frame.get(first).is_none());
+        assert_eq!(writer.marker(), 0);
+    }
+}
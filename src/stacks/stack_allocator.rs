@@ -6,13 +6,28 @@
 // copied, modified, or distributed except according to those terms.
 
 use core::ptr;
-use std::cell::{BorrowError, RefCell};
-use std::mem;
-
-use allocation_error::{AllocationError, AllocationResult};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::fmt;
+use std::marker::PhantomData;
+use std::convert::Infallible;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Add, Deref};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use allocation_error::{AllocationError, AllocationResult, StorageKind, TryAllocError};
 use utils;
-use memory_chunk::MemoryChunk;
+use memory_chunk::{Allocations, MemoryChunk};
+use memory_dump::{self, DumpFormat, StorageLayout};
+use stacks::frozen_stack::FrozenStack;
 use std::intrinsics::needs_drop;
+#[cfg(feature = "allocation_tracking")]
+use std::panic::Location;
+#[cfg(feature = "allocation_tracking")]
+use allocation_tracking::{AllocationReport, CallSiteTracker};
 
 /// A stack-based allocator.
 ///
@@ -47,7 +62,7 @@ use std::intrinsics::needs_drop;
 ///
 /// - And return an immutable/mutable reference to the object which has been placed in the memory storage.
 ///
-/// This offset is calculated by the size of the object, its vtable (if the object implement the `Drop` trait),
+/// This offset is calculated by the size of the object, its header and footer (if the object implement the `Drop` trait),
 /// its memory-alignment and an offset to align the object in memory.
 ///
 /// ## Roll-back
@@ -68,6 +83,19 @@ use std::intrinsics::needs_drop;
 ///
 /// - Set the first unused memory address to the bottom of its stack.
 ///
+/// ## Destruction order
+///
+/// The objects are dropped in the reverse order of their allocation, the most recent first. An object can safely
+/// refer to the objects allocated before it: they are still alive when it is dropped.
+///
+/// To walk the memory storage backward, a footer holding the location of the header is placed after every object
+/// implementing the `Drop` trait.
+///
+/// While a memory storage is being reset, the destructors of its objects cannot use it: allocating data implementing
+/// the `Drop` trait or resetting the memory storage from a destructor returns a `ResetInProgressError`, or panics
+/// for the functions which cannot return an error. The memory storage holding data implementing the `Copy` trait can
+/// still be used.
+///
 /// # Example
 ///
 /// ```rust
@@ -105,6 +133,129 @@ use std::intrinsics::needs_drop;
 pub struct StackAllocator {
     storage: RefCell<MemoryChunk>,
     storage_copy: RefCell<MemoryChunk>,
+    /// The memory lent to the child allocators and the sub-allocators.
+    children: Mutex<Vec<LentMemory>>,
+    /// The unique identifier of the allocator, recorded in the handles to its data.
+    id: usize,
+    /// The number of resets of the allocator, recorded in the handles to its data to detect the stale ones.
+    generation: Cell<usize>,
+    #[cfg(feature = "allocation_tracking")]
+    call_sites: CallSiteTracker,
+    #[cfg(feature = "allocation_tracking")]
+    call_sites_copy: CallSiteTracker,
+}
+
+/// The next identifier given to a stack allocator. The identifiers are unique for the lifetime of the program.
+static NEXT_ALLOCATOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A handle to data allocated by a `StackAllocator`, created by `StackAllocator::handle()`.
+///
+/// The handle can be resolved by the allocator, or by its frozen view, until the allocator is reset.
+pub struct StackHandle<T> {
+    /// The identifier of the allocator.
+    allocator: usize,
+    /// The number of resets of the allocator when the handle was created.
+    generation: usize,
+    storage: StorageKind,
+    /// The index of the data in the memory storage.
+    offset: usize,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for StackHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StackHandle<T> {}
+
+impl<T> fmt::Debug for StackHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "StackHandle [Allocator: {}, Generation: {}, Storage: {}, Offset: {}]",
+            self.allocator, self.generation, self.storage, self.offset
+        )
+    }
+}
+
+/// The uninitialized memory reserved for data allocated in place, given to the closure of `StackAllocator::alloc_with_ptr()`.
+///
+/// Initializing the memory gives an `Initialized` proof, which the closure returns to confirm the initialization.
+pub struct InPlace<'s, T: 's> {
+    slot: &'s mut MaybeUninit<T>,
+    _brand: PhantomData<fn(&'s ()) -> &'s ()>,
+}
+
+/// The proof that the memory given to the closure of `StackAllocator::alloc_with_ptr()` has been initialized.
+///
+/// A proof can only be created by the `InPlace` memory it belongs to.
+#[derive(Debug)]
+pub struct Initialized<'s> {
+    _brand: PhantomData<fn(&'s ()) -> &'s ()>,
+}
+
+impl<'s, T: 's> InPlace<'s, T> {
+    fn new(slot: &'s mut MaybeUninit<T>) -> Self {
+        InPlace {
+            slot,
+            _brand: PhantomData,
+        }
+    }
+
+    /// Initializes the memory with the given value.
+    pub fn write(self, value: T) -> Initialized<'s> {
+        self.slot.write(value);
+        Initialized { _brand: PhantomData }
+    }
+
+    /// Returns a raw pointer to the uninitialized memory, to initialize the data field by field.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.slot.as_mut_ptr()
+    }
+
+    /// Confirms that the memory has been initialized through `as_mut_ptr()`.
+    ///
+    /// # Safety
+    /// The data must be fully initialized.
+    pub unsafe fn assume_init(self) -> Initialized<'s> {
+        Initialized { _brand: PhantomData }
+    }
+}
+
+impl<'s, T: 's> fmt::Debug for InPlace<'s, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InPlace [Location: {:p}]", self.slot.as_ptr())
+    }
+}
+
+/// The memory used by an allocator, in bytes.
+///
+/// The statistics of several allocators can be added together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AllocatorStatistics {
+    /// The number of bytes used in the memory storage holding data implementing the `Drop` trait.
+    pub used: usize,
+    /// The capacity of the memory storage holding data implementing the `Drop` trait.
+    pub capacity: usize,
+    /// The number of bytes used in the memory storage holding data implementing the `Copy` trait.
+    pub used_copy: usize,
+    /// The capacity of the memory storage holding data implementing the `Copy` trait.
+    pub capacity_copy: usize,
+}
+
+impl Add for AllocatorStatistics {
+    type Output = AllocatorStatistics;
+
+    fn add(self, other: AllocatorStatistics) -> AllocatorStatistics {
+        AllocatorStatistics {
+            used: self.used + other.used,
+            capacity: self.capacity + other.capacity,
+            used_copy: self.used_copy + other.used_copy,
+            capacity_copy: self.capacity_copy + other.capacity_copy,
+        }
+    }
 }
 
 impl StackAllocator {
@@ -126,6 +277,13 @@ impl StackAllocator {
         StackAllocator {
             storage: RefCell::new(MemoryChunk::new(capacity)),
             storage_copy: RefCell::new(MemoryChunk::new(capacity_copy)),
+            children: Mutex::new(Vec::new()),
+            id: NEXT_ALLOCATOR_ID.fetch_add(1, Ordering::Relaxed),
+            generation: Cell::new(0),
+            #[cfg(feature = "allocation_tracking")]
+            call_sites: CallSiteTracker::new(),
+            #[cfg(feature = "allocation_tracking")]
+            call_sites_copy: CallSiteTracker::new(),
         }
     }
 
@@ -134,8 +292,11 @@ impl StackAllocator {
     /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
     /// Otherwise, it will be placed in the other memory storage.
     ///
+    /// If the closure panics, the memory reserved for the data is given back to the allocator.
+    ///
     /// # Error
     /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    /// It will also return an error if droppable data is allocated by the destructor of an object being dropped by a reset.
     ///
     /// # Example
     /// ```
@@ -155,6 +316,7 @@ impl StackAllocator {
     /// #   try_main().unwrap();
     /// # }
     /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
     where
         F: FnOnce() -> T,
@@ -163,10 +325,10 @@ impl StackAllocator {
         unsafe {
             if needs_drop::<T>() {
                 trace!("The data to allocate is droppable.");
-                self.alloc_non_copy_mut(op)
+                self.alloc_non_copy_mut(|| Ok(op())).map_err(TryAllocError::into_allocation_error)
             } else {
                 trace!("The data to allocate is copyable.");
-                self.alloc_copy_mut(op)
+                self.alloc_copy_mut(|| Ok(op())).map_err(TryAllocError::into_allocation_error)
             }
         }
     }
@@ -176,12 +338,18 @@ impl StackAllocator {
     /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
     /// Otherwise, it will be placed in the other memory storage.
     ///
+    /// If the closure panics, the memory reserved for the data is given back to the allocator.
+    ///
     /// # Warning
     /// This function doesn't return an error if the allocated data doesn't fit in the `StackAllocator`'s remaining capacity,
     /// It doesn't perform any check.
     ///
     /// Use if you now that the data will fit into memory and you can't afford the checks.
     ///
+    /// # Panic
+    /// This function will panic if the memory location of the data cannot be represented by a `usize`.
+    /// It will also panic if droppable data is allocated by the destructor of an object being dropped by a reset.
+    ///
     /// # Example
     /// ```
     /// use maskerad_memory_allocators::StackAllocator;
@@ -204,6 +372,7 @@ impl StackAllocator {
     /// #   try_main().unwrap();
     /// # }
     /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc_mut_unchecked<T, F>(&self, op: F) -> &mut T
         where
             F: FnOnce() -> T,
@@ -212,58 +381,80 @@ impl StackAllocator {
         unsafe {
             if needs_drop::<T>() {
                 trace!("The data to allocate is droppable.");
-                self.alloc_non_copy_mut_unchecked(op)
+                unwrap_infallible(self.alloc_non_copy_mut_unchecked(|| Ok(op())))
             } else {
                 trace!("The data to allocate is copyable.");
-                self.alloc_copy_mut_unchecked(op)
+                unwrap_infallible(self.alloc_copy_mut_unchecked(|| Ok(op())))
             }
         }
     }
 
     /// The function actually writing data in the memory storage
-    fn alloc_non_copy_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_non_copy_mut<T, E, F>(&self, op: F) -> Result<&mut T, TryAllocError<E>>
     where
-        F: FnOnce() -> T,
+        F: FnOnce() -> Result<T, E>,
     {
         trace!("Allocating mutable and droppable data.");
         unsafe {
-            //Get the type description of the type T (get its vtable).
-            trace!("Getting a TypeDescription of the data being allocated.");
-            let type_description = utils::get_type_description::<T>();
-
-            //Ask the memory chunk to give us raw pointers to memory locations for our type description and object
-            trace!("Getting raw pointers to memory locations, to store the type description and the data.");
-            let (type_description_ptr, ptr) =
-                self.alloc_non_copy_inner(mem::size_of::<T>(), mem::align_of::<T>())?;
-
-            //Cast them.
-            trace!("Casting the raw pointers to appropriate types.");
-            let type_description_ptr = type_description_ptr as *mut usize;
-            let ptr = ptr as *mut T;
-
-            //write in our type description along with a bit indicating that the object has *not*
-            //been initialized yet.
-            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to false.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, false);
-
-            //Initialize the object.
-            trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
-
-            //Now that we are done, update the type description to indicate
-            //that the object is there.
-            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to true.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, true);
-
-            //Return a mutable reference to the object.
-            trace!("Returning a mutable reference to the allocated data.");
-            Ok(&mut *ptr)
+            //The object is initialized if the initializer doesn't return an error.
+            self.alloc_non_copy_in_place(|slot: &mut MaybeUninit<T>| {
+                ptr::write(slot.as_mut_ptr(), op()?);
+                Ok(())
+            })
         }
     }
 
-    fn alloc_non_copy_mut_unchecked<T, F>(&self, op: F) -> &mut T
+    /// Reserves memory for droppable data, and gives it to the initializer.
+    ///
+    /// The initializer must have initialized the data if it returns `Ok`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    unsafe fn alloc_non_copy_in_place<T, E, F>(&self, init: F) -> Result<&mut T, TryAllocError<E>>
+    where
+        F: FnOnce(&mut MaybeUninit<T>) -> Result<(), E>,
+    {
+        trace!("Allocating droppable data, initialized in place.");
+        //Get the type description of the type T (get its vtable).
+        trace!("Getting a TypeDescription of the data being allocated.");
+        let type_description = utils::get_type_description::<T>();
+
+        //Ask the memory chunk to give us raw pointers to memory locations for our header and object
+        trace!("Getting raw pointers to memory locations, to store the header and the data.");
+        let start = self.storage.borrow().fill();
+        let (header_ptr, ptr) =
+            self.alloc_non_copy_inner(mem::size_of::<T>(), mem::align_of::<T>())?;
+
+        //Cast them.
+        trace!("Casting the raw pointers to appropriate types.");
+        let header_ptr = header_ptr as *mut utils::AllocationHeader;
+        let ptr = ptr as *mut T;
+
+        //write in our header the type description, along with a bit indicating that the object has *not*
+        //been initialized yet.
+        trace!("Packing in the low bit of the TypeDescription the 'is_done' state to false.");
+        ptr::write(header_ptr, utils::AllocationHeader::new::<T>(type_description));
+
+        //Initialize the object.
+        //If the initializer panics or fails, the guard gives the memory back to the allocator.
+        trace!("Initializing the data.");
+        let guard = RollbackGuard::new(self, StorageKind::Drop, start);
+        init(&mut *(ptr as *mut MaybeUninit<T>)).map_err(TryAllocError::InitializerError)?;
+        guard.disarm();
+
+        //Now that we are done, update the type description to indicate
+        //that the object is there.
+        trace!("Packing in the low bit of the TypeDescription the 'is_done' state to true.");
+        (*header_ptr).type_description = utils::bitpack_type_description_ptr(type_description, true);
+
+        //Return a mutable reference to the object.
+        trace!("Returning a mutable reference to the allocated data.");
+        Ok(&mut *ptr)
+    }
+
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_non_copy_mut_unchecked<T, E, F>(&self, op: F) -> Result<&mut T, E>
         where
-            F: FnOnce() -> T,
+            F: FnOnce() -> Result<T, E>,
     {
         trace!("Allocating mutable and droppable data (unchecked).");
         unsafe {
@@ -271,69 +462,96 @@ impl StackAllocator {
             trace!("Getting a TypeDescription of the data being allocated.");
             let type_description = utils::get_type_description::<T>();
 
-            //Ask the memory chunk to give us raw pointers to memory locations for our type description and object
-            trace!("Getting raw pointers to memory locations, to store the type description and the data.");
-            let (type_description_ptr, ptr) =
+            //Ask the memory chunk to give us raw pointers to memory locations for our header and object
+            trace!("Getting raw pointers to memory locations, to store the header and the data.");
+            let start = self.storage.borrow().fill();
+            let (header_ptr, ptr) =
                 self.alloc_non_copy_inner_unchecked(mem::size_of::<T>(), mem::align_of::<T>());
 
             //Cast them.
             trace!("Casting the raw pointers to appropriate types.");
-            let type_description_ptr = type_description_ptr as *mut usize;
+            let header_ptr = header_ptr as *mut utils::AllocationHeader;
             let ptr = ptr as *mut T;
 
-            //write in our type description along with a bit indicating that the object has *not*
+            //write in our header the type description, along with a bit indicating that the object has *not*
             //been initialized yet.
             trace!("Packing in the low bit of the TypeDescription the 'is_done' state to false.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, false);
+            ptr::write(header_ptr, utils::AllocationHeader::new::<T>(type_description));
 
             //Initialize the object.
+            //If the initializer panics or fails, the guard gives the memory back to the allocator.
             trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
+            let guard = RollbackGuard::new(self, StorageKind::Drop, start);
+            ptr::write(&mut (*ptr), op()?);
+            guard.disarm();
 
             //Now that we are done, update the type description to indicate
             //that the object is there.
             trace!("Packing in the low bit of the TypeDescription the 'is_done' state to true.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, true);
+            (*header_ptr).type_description = utils::bitpack_type_description_ptr(type_description, true);
 
             //Return a mutable reference to the object.
             trace!("Returning a mutable reference to the allocated data.");
-            &mut *ptr
+            Ok(&mut *ptr)
         }
     }
 
     //Functions for the copyable part of the stack allocator.
-    fn alloc_copy_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_copy_mut<T, E, F>(&self, op: F) -> Result<&mut T, TryAllocError<E>>
     where
-        F: FnOnce() -> T,
+        F: FnOnce() -> Result<T, E>,
     {
         trace!("Allocating mutable and copyable data.");
         unsafe {
-            //Get an aligned raw pointer to place the object in it.
-            trace!("Getting a raw pointer to a memory location, to store the data.");
-            let ptr = self.alloc_copy_inner(mem::size_of::<T>(), mem::align_of::<T>())?;
-
-            //cast this raw pointer to the type of the object.
-            trace!("Casting the raw pointer to appropriate type.");
-            let ptr = ptr as *mut T;
-
-            //Write the data in the memory location.
-            trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
-
-            //return a mutable reference to this pointer.
-            trace!("Returning a mutable reference to the allocated data.");
-            Ok(&mut *ptr)
+            //The object is initialized if the initializer doesn't return an error.
+            self.alloc_copy_in_place(|slot: &mut MaybeUninit<T>| {
+                ptr::write(slot.as_mut_ptr(), op()?);
+                Ok(())
+            })
         }
     }
 
-    fn alloc_copy_mut_unchecked<T, F>(&self, op: F) -> &mut T
+    /// Reserves memory for copyable data, and gives it to the initializer.
+    ///
+    /// The initializer must have initialized the data if it returns `Ok`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    unsafe fn alloc_copy_in_place<T, E, F>(&self, init: F) -> Result<&mut T, TryAllocError<E>>
+    where
+        F: FnOnce(&mut MaybeUninit<T>) -> Result<(), E>,
+    {
+        trace!("Allocating copyable data, initialized in place.");
+        //Get an aligned raw pointer to place the object in it.
+        trace!("Getting a raw pointer to a memory location, to store the data.");
+        let start = self.storage_copy.borrow().fill();
+        let ptr = self.alloc_copy_inner(mem::size_of::<T>(), mem::align_of::<T>())?;
+
+        //cast this raw pointer to the type of the object.
+        trace!("Casting the raw pointer to appropriate type.");
+        let ptr = ptr as *mut T;
+
+        //Write the data in the memory location.
+        //If the initializer panics or fails, the guard gives the memory back to the allocator.
+        trace!("Initializing the data.");
+        let guard = RollbackGuard::new(self, StorageKind::Copy, start);
+        init(&mut *(ptr as *mut MaybeUninit<T>)).map_err(TryAllocError::InitializerError)?;
+        guard.disarm();
+
+        //return a mutable reference to this pointer.
+        trace!("Returning a mutable reference to the allocated data.");
+        Ok(&mut *ptr)
+    }
+
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_copy_mut_unchecked<T, E, F>(&self, op: F) -> Result<&mut T, E>
         where
-            F: FnOnce() -> T,
+            F: FnOnce() -> Result<T, E>,
     {
         trace!("Allocating mutable and copyable data (unchecked).");
         unsafe {
             //Get an aligned raw pointer to place the object in it.
             trace!("Getting a raw pointer to a memory location, to store the data.");
+            let start = self.storage_copy.borrow().fill();
             let ptr = self.alloc_copy_inner_unchecked(mem::size_of::<T>(), mem::align_of::<T>());
 
             //cast this raw pointer to the type of the object.
@@ -341,12 +559,15 @@ impl StackAllocator {
             let ptr = ptr as *mut T;
 
             //Write the data in the memory location.
+            //If the initializer panics or fails, the guard gives the memory back to the allocator.
             trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
+            let guard = RollbackGuard::new(self, StorageKind::Copy, start);
+            ptr::write(&mut (*ptr), op()?);
+            guard.disarm();
 
             //return a mutable reference to this pointer.
             trace!("Returning a mutable reference to the allocated data.");
-            &mut *ptr
+            Ok(&mut *ptr)
         }
     }
 
@@ -355,8 +576,11 @@ impl StackAllocator {
     /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
     /// Otherwise, it will be placed in the other memory storage.
     ///
+    /// If the closure panics, the memory reserved for the data is given back to the allocator.
+    ///
     /// # Error
     /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    /// It will also return an error if droppable data is allocated by the destructor of an object being dropped by a reset.
     ///
     /// # Example
     ///
@@ -377,6 +601,7 @@ impl StackAllocator {
     /// #   try_main().unwrap();
     /// # }
     /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&T>
     where
         F: FnOnce() -> T,
@@ -398,12 +623,18 @@ impl StackAllocator {
     /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
     /// Otherwise, it will be placed in the other memory storage.
     ///
+    /// If the closure panics, the memory reserved for the data is given back to the allocator.
+    ///
     /// # Warning
     /// This function doesn't return an error if the allocated data doesn't fit in the `StackAllocator`'s remaining capacity,
     /// It doesn't perform any check.
     ///
     /// Use if you now that the data will fit into memory and you can't afford the checks.
     ///
+    /// # Panic
+    /// This function will panic if the memory location of the data cannot be represented by a `usize`.
+    /// It will also panic if droppable data is allocated by the destructor of an object being dropped by a reset.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -427,6 +658,7 @@ impl StackAllocator {
     /// #   try_main().unwrap();
     /// # }
     /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     pub fn alloc_unchecked<T, F>(&self, op: F) -> &T
         where
             F: FnOnce() -> T,
@@ -443,139 +675,258 @@ impl StackAllocator {
         }
     }
 
-    //Functions for the non-copyable part of the arena.
-
-    /// The function actually writing data in the memory storage
-    fn alloc_non_copy<T, F>(&self, op: F) -> AllocationResult<&T>
+    /// Allocates data in the allocator's memory with a fallible initializer, returning an immutable reference
+    /// to the allocated data.
+    ///
+    /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
+    /// Otherwise, it will be placed in the other memory storage.
+    ///
+    /// If the closure returns an error or panics, the memory reserved for the data is given back to the allocator.
+    ///
+    /// # Error
+    /// This function will return an `AllocationError` if the allocation exceeds the maximum storage capacity of the allocator,
+    /// or if droppable data is allocated by the destructor of an object being dropped by a reset.
+    /// It will return an `InitializerError` holding the error returned by the closure.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::{StackAllocator, TryAllocError};
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 100);
+    ///
+    /// let my_i32 = allocator.try_alloc_with(|| "26".parse::<i32>())?;
+    /// assert_eq!(my_i32, &26);
+    ///
+    /// let marker = allocator.marker_copy();
+    /// let not_an_i32 = allocator.try_alloc_with(|| "twenty-six".parse::<i32>());
+    /// match not_an_i32 {
+    ///     Err(TryAllocError::InitializerError(_)) => {},
+    ///     _ => panic!("The initializer should have failed."),
+    /// }
+    ///
+    /// // The memory has been given back.
+    /// assert_eq!(allocator.marker_copy(), marker);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn try_alloc_with<T, E, F>(&self, op: F) -> Result<&T, TryAllocError<E>>
     where
-        F: FnOnce() -> T,
+        F: FnOnce() -> Result<T, E>,
     {
-        trace!("Allocating immutable and droppable data.");
-        unsafe {
-            //Get the type description of the type T (get its vtable).
-            trace!("Getting a TypeDescription of the data being allocated.");
-            let type_description = utils::get_type_description::<T>();
-
-            //Ask the memory chunk to give us raw pointers to memory locations for our type description and object
-            trace!("Getting raw pointers to memory locations, to store the type description and the data.");
-            let (type_description_ptr, ptr) =
-                self.alloc_non_copy_inner(mem::size_of::<T>(), mem::align_of::<T>())?;
-
-            //Cast them.
-            trace!("Casting the raw pointers to appropriate types.");
-            let type_description_ptr = type_description_ptr as *mut usize;
-            let ptr = ptr as *mut T;
-
-            //write in our type description along with a bit indicating that the object has *not*
-            //been initialized yet.
-            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to false.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, false);
-
-            //Initialize the object.
-            trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
-
-            //Now that we are done, update the type description to indicate
-            //that the object is there.
-            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to true.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, true);
-
-            //Return a mutable reference to the object.
-            trace!("Returning an immutable reference to the allocated data.");
-            Ok(&*ptr)
-        }
+        debug!("Allocating data with a fallible initializer, returning an immutable reference.");
+        self.try_alloc_mut_with(op).map(|object| &*object)
     }
 
-    fn alloc_non_copy_unchecked<T, F>(&self, op: F) -> &T
-        where
-            F: FnOnce() -> T,
+    /// Allocates data in the allocator's memory with a fallible initializer, returning a mutable reference
+    /// to the allocated data.
+    ///
+    /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
+    /// Otherwise, it will be placed in the other memory storage.
+    ///
+    /// If the closure returns an error or panics, the memory reserved for the data is given back to the allocator.
+    ///
+    /// # Error
+    /// This function will return an `AllocationError` if the allocation exceeds the maximum storage capacity of the allocator,
+    /// or if droppable data is allocated by the destructor of an object being dropped by a reset.
+    /// It will return an `InitializerError` holding the error returned by the closure.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 100);
+    ///
+    /// let my_string = allocator.try_alloc_mut_with(|| String::from_utf8(vec![0x26]))?;
+    /// my_string.push('&');
+    ///
+    /// assert_eq!(my_string, "&&");
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn try_alloc_mut_with<T, E, F>(&self, op: F) -> Result<&mut T, TryAllocError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
     {
-        trace!("Allocating immutable and droppable data (unchecked).");
+        debug!("Allocating data with a fallible initializer, returning a mutable reference.");
         unsafe {
-            //Get the type description of the type T (get its vtable).
-            trace!("Getting a TypeDescription of the data being allocated.");
-            let type_description = utils::get_type_description::<T>();
-
-            //Ask the memory chunk to give us raw pointers to memory locations for our type description and object
-            trace!("Getting raw pointers to memory locations, to store the type description and the data.");
-            let (type_description_ptr, ptr) =
-                self.alloc_non_copy_inner_unchecked(mem::size_of::<T>(), mem::align_of::<T>());
-
-            //Cast them.
-            trace!("Casting the raw pointers to appropriate types.");
-            let type_description_ptr = type_description_ptr as *mut usize;
-            let ptr = ptr as *mut T;
-
-            //write in our type description along with a bit indicating that the object has *not*
-            //been initialized yet.
-            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to false.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, false);
-
-            //Initialize the object.
-            trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
-
-            //Now that we are done, update the type description to indicate
-            //that the object is there.
-            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to true.");
-            *type_description_ptr = utils::bitpack_type_description_ptr(type_description, true);
-
-            //Return an immutable reference to the object.
-            trace!("Returning an immutable reference to the allocated data.");
-            &*ptr
+            if needs_drop::<T>() {
+                trace!("The data to allocate is droppable.");
+                self.alloc_non_copy_mut(op)
+            } else {
+                trace!("The data to allocate is copyable.");
+                self.alloc_copy_mut(op)
+            }
         }
     }
 
-    fn alloc_copy<T, F>(&self, op: F) -> AllocationResult<&T>
-    where
-        F: FnOnce() -> T,
-    {
-        trace!("Allocating immutable and copyable data.");
+    /// Reserves memory for data implementing the `Copy` trait, returning a mutable reference to the uninitialized data.
+    ///
+    /// The data is placed in the memory storage storing data implementing the `Copy` trait. It can be initialized
+    /// in place, without moving it through the stack.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    ///
+    /// # Example
+    /// ```
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 5000);
+    ///
+    /// let big_array = allocator.alloc_uninit::<[u64; 512]>()?;
+    /// let big_array = unsafe {
+    ///     let first = big_array.as_mut_ptr() as *mut u64;
+    ///     for i in 0..512 {
+    ///         first.offset(i as isize).write(i);
+    ///     }
+    ///     &*big_array.as_ptr()
+    /// };
+    ///
+    /// assert_eq!(big_array[511], 511);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_uninit<T: Copy>(&self) -> AllocationResult<&mut MaybeUninit<T>> {
+        debug!("Allocating uninitialized and copyable data.");
         unsafe {
             //Get an aligned raw pointer to place the object in it.
             trace!("Getting a raw pointer to a memory location, to store the data.");
             let ptr = self.alloc_copy_inner(mem::size_of::<T>(), mem::align_of::<T>())?;
 
-            //cast this raw pointer to the type of the object.
-            trace!("Casting the raw pointer to the appropriate type.");
-            let ptr = ptr as *mut T;
-
-            //Write the data in the memory location.
-            trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
-
-            //return a mutable reference to this pointer.
-            trace!("Returning an immutable reference to the allocated data.");
-            Ok(&*ptr)
+            //The data is not initialized, there is nothing to write.
+            trace!("Returning a mutable reference to the uninitialized data.");
+            Ok(&mut *(ptr as *mut MaybeUninit<T>))
         }
     }
 
-    fn alloc_copy_unchecked<T, F>(&self, op: F) -> &T
-        where
-            F: FnOnce() -> T,
-    {
-        trace!("Allocating immutable and copyable data (unchecked).");
-        unsafe {
-            //Get an aligned raw pointer to place the object in it.
-            trace!("Getting a raw pointer to a memory location, to store the data.");
-            let ptr = self.alloc_copy_inner_unchecked(mem::size_of::<T>(), mem::align_of::<T>());
-
-            //cast this raw pointer to the type of the object.
-            trace!("Casting the raw pointer to the appropriate type.");
-            let ptr = ptr as *mut T;
-
-            //Write the data in the memory location.
-            trace!("Initializing the data.");
-            ptr::write(&mut (*ptr), op());
-
-            //return an immutable reference to this pointer.
-            trace!("Returning an immutable reference to the allocated data.");
-            &*ptr
-        }
+    /// Allocates data in the allocator's memory, initializing it in place. Returns a mutable reference to the
+    /// allocated data.
+    ///
+    /// The closure is given the uninitialized memory reserved for the data. It confirms the initialization by returning
+    /// the `Initialized` proof given by the memory, or declines it by returning an error.
+    /// The data is only considered initialized, and will only be dropped, once the closure confirmed the initialization.
+    ///
+    /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
+    /// Otherwise, it will be placed in the other memory storage.
+    ///
+    /// If the closure returns an error or panics, the memory reserved for the data is given back to the allocator.
+    ///
+    /// # Error
+    /// This function will return an `AllocationError` if the allocation exceeds the maximum storage capacity of the allocator,
+    /// or if droppable data is allocated by the destructor of an object being dropped by a reset.
+    /// It will return an `InitializerError` holding the error returned by the closure.
+    ///
+    /// # Example
+    /// ```
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// use std::convert::Infallible;
+    /// use std::ptr;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 100);
+    ///
+    /// let my_string = allocator.alloc_with_ptr(|slot| -> Result<_, Infallible> {
+    ///     Ok(slot.write(String::from("in place")))
+    /// })?;
+    /// assert_eq!(my_string, "in place");
+    ///
+    /// // The data can be initialized field by field.
+    /// let my_pair = allocator.alloc_with_ptr(|mut slot| -> Result<_, Infallible> {
+    ///     let pair: *mut (u32, u64) = slot.as_mut_ptr();
+    ///     unsafe {
+    ///         ptr::addr_of_mut!((*pair).0).write(4);
+    ///         ptr::addr_of_mut!((*pair).1).write(2);
+    ///         Ok(slot.assume_init())
+    ///     }
+    /// })?;
+    /// assert_eq!(*my_pair, (4, 2));
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_with_ptr<T, E, F>(&self, op: F) -> Result<&mut T, TryAllocError<E>>
+    where
+        F: for<'s> FnOnce(InPlace<'s, T>) -> Result<Initialized<'s>, E>,
+    {
+        debug!("Allocating data initialized in place, returning a mutable reference.");
+        //The data is initialized once the closure returned a proof, which only its memory can give.
+        unsafe {
+            if needs_drop::<T>() {
+                trace!("The data to allocate is droppable.");
+                self.alloc_non_copy_in_place(|slot| op(InPlace::new(slot)).map(|_| ()))
+            } else {
+                trace!("The data to allocate is copyable.");
+                self.alloc_copy_in_place(|slot| op(InPlace::new(slot)).map(|_| ()))
+            }
+        }
+    }
+
+    //Functions for the non-copyable part of the arena.
+
+    /// The function actually writing data in the memory storage
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_non_copy<T, F>(&self, op: F) -> AllocationResult<&T>
+    where
+        F: FnOnce() -> T,
+    {
+        trace!("Allocating immutable and droppable data.");
+        self.alloc_non_copy_mut(|| Ok(op()))
+            .map(|object| &*object)
+            .map_err(TryAllocError::into_allocation_error)
+    }
+
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_non_copy_unchecked<T, F>(&self, op: F) -> &T
+        where
+            F: FnOnce() -> T,
+    {
+        trace!("Allocating immutable and droppable data (unchecked).");
+        &*unwrap_infallible(self.alloc_non_copy_mut_unchecked(|| Ok(op())))
+    }
+
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_copy<T, F>(&self, op: F) -> AllocationResult<&T>
+    where
+        F: FnOnce() -> T,
+    {
+        trace!("Allocating immutable and copyable data.");
+        self.alloc_copy_mut(|| Ok(op()))
+            .map(|object| &*object)
+            .map_err(TryAllocError::into_allocation_error)
+    }
+
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn alloc_copy_unchecked<T, F>(&self, op: F) -> &T
+        where
+            F: FnOnce() -> T,
+    {
+        trace!("Allocating immutable and copyable data (unchecked).");
+        &*unwrap_infallible(self.alloc_copy_mut_unchecked(|| Ok(op())))
     }
 
     /// The function asking the memory storage to give us raw pointers to memory locations and update
     /// the current top of the stack.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     fn alloc_non_copy_inner(
         &self,
         n_bytes: usize,
@@ -585,40 +936,67 @@ impl StackAllocator {
         trace!("Borrowing a reference to the memory chunk storing droppable data.");
         let non_copy_storage = self.storage.borrow();
 
+        //The destructors of the objects of the memory chunk cannot allocate in it.
+        if non_copy_storage.is_destroying() {
+            error!("The memory chunk is dropping its objects, the data cannot be allocated !");
+            return Err(AllocationError::ResetInProgressError {
+                storage: StorageKind::Drop,
+            });
+        }
+
         //Get the index of the first unused byte in the memory chunk.
         trace!("Getting the index of the first unused byte in the memory chunk.");
         let fill = non_copy_storage.fill();
 
-        //Get the index of where we'll write the type description data
+        //Get the index of where we'll write the header
         //(the first unused byte in the memory chunk).
-        trace!("The memory location for the TypeDescription will begin at byte {} ({:x})...", fill, fill);
-        let type_description_start = fill;
+        trace!("The memory location for the header will begin at byte {} ({:x})...", fill, fill);
+        let header_start = fill;
+
+        //The error returned if one of the indices below can't be represented.
+        let overflow = AllocationError::CapacityOverflowError {
+            requested_size: n_bytes,
+            alignment: align,
+            storage: StorageKind::Drop,
+        };
 
         // Get the index of where the object should reside (unaligned location actually).
-        let after_type_description = fill + mem::size_of::<*const utils::TypeDescription>();
-        trace!("...and will end at {} ({:x})", after_type_description, after_type_description);
+        let after_header = fill
+            .checked_add(mem::size_of::<utils::AllocationHeader>())
+            .ok_or(overflow)?;
+        trace!("...and will end at {} ({:x})", after_header, after_header);
 
         //With the index to the unaligned memory address, determine the index to
         //the aligned memory address where the object will reside,
         //according to its memory alignment.
-        let start = utils::round_up(after_type_description, align);
+        let start = utils::round_up(after_header, align).ok_or(overflow)?;
         trace!("The memory location for the actual data will begin at byte {} ({:x})...", start, start);
 
-        //Determine the index of the next aligned memory address for a type description, according to the size of the object
-        //and the memory alignment of a type description.
-        let end = utils::round_up(
-            start + n_bytes,
-            mem::align_of::<*const utils::TypeDescription>(),
-        );
+        //Determine the index of the footer, according to the size of the object
+        //and the memory alignment of a footer.
+        let footer_start = start
+            .checked_add(n_bytes)
+            .and_then(|end| utils::round_up(end, mem::align_of::<utils::AllocationFooter>()))
+            .ok_or(overflow)?;
+        trace!("The memory location for the footer will begin at byte {} ({:x})...", footer_start, footer_start);
+
+        //The next header lies just after the footer, they have the same alignment.
+        let end = footer_start
+            .checked_add(mem::size_of::<utils::AllocationFooter>())
+            .ok_or(overflow)?;
         trace!("...and will end at {} ({:x})", end, end);
 
         //If the allocator becomes oom after this possible allocation, abort the program.
         trace!("Checking if the allocator has enough remaining memory to store the data.");
         if end >= non_copy_storage.capacity() {
             error!("The allocator doesn't have enough remaining memory to store the data !");
-            return Err(AllocationError::OutOfMemoryError(format!(
-                "The stack allocator is out of memory !"
-            )));
+            return Err(AllocationError::OutOfMemoryError {
+                requested_size: n_bytes,
+                alignment: align,
+                storage: StorageKind::Drop,
+                fill,
+                capacity: non_copy_storage.capacity(),
+            });
         }
 
         //Update the current top of the stack.
@@ -628,24 +1006,35 @@ impl StackAllocator {
         trace!("Setting the first unused byte of memory of the memory chunk to byte {} ({:x})", end, end);
         non_copy_storage.set_fill(end);
 
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites.record(Location::caller(), header_start, end);
+
         unsafe {
             // Get a raw pointer to the start of our MemoryChunk's RawVec
             let start_storage = non_copy_storage.as_ptr();
             trace!("Getting a raw pointer to the start of the allocation of the memory chunk: {:p}.", start_storage);
 
-            trace!("Returning a tuple of raw pointers to memory locations for the TypeDescription and data.");
+            //Write the footer, linking the end of the allocation to its header.
+            trace!("Writing the footer, holding the index of the header.");
+            ptr::write(
+                start_storage.offset(footer_start as isize) as *mut utils::AllocationFooter,
+                utils::AllocationFooter { header: header_start },
+            );
+
+            trace!("Returning a tuple of raw pointers to memory locations for the header and data.");
             Ok((
                 //From this raw pointer, get the correct raw pointers with
                         //the indices we calculated earlier.
 
-                        //The raw pointer to the type description of the object.
-                start_storage.offset(type_description_start as isize),
+                        //The raw pointer to the header of the object.
+                start_storage.offset(header_start as isize),
                 //The raw pointer to the object.
                 start_storage.offset(start as isize),
             ))
         }
     }
 
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     fn alloc_non_copy_inner_unchecked(
         &self,
         n_bytes: usize,
@@ -655,31 +1044,44 @@ impl StackAllocator {
         trace!("Borrowing a reference to the memory chunk storing droppable data.");
         let non_copy_storage = self.storage.borrow();
 
+        //The destructors of the objects of the memory chunk cannot allocate in it.
+        if non_copy_storage.is_destroying() {
+            panic!("Droppable data cannot be allocated while the objects of the memory storage are being dropped.");
+        }
+
         //Get the index of the first unused byte in the memory chunk.
         trace!("Getting the index of the first unused byte in the memory chunk.");
         let fill = non_copy_storage.fill();
 
-        //Get the index of where we'll write the type description data
+        //Get the index of where we'll write the header
         //(the first unused byte in the memory chunk).
-        trace!("The memory location for the TypeDescription will begin at byte {} ({:x})...", fill, fill);
-        let type_description_start = fill;
+        trace!("The memory location for the header will begin at byte {} ({:x})...", fill, fill);
+        let header_start = fill;
 
         // Get the index of where the object should reside (unaligned location actually).
-        let after_type_description = fill + mem::size_of::<*const utils::TypeDescription>();
-        trace!("...and will end at {} ({:x})", after_type_description, after_type_description);
+        let after_header = fill
+            .checked_add(mem::size_of::<utils::AllocationHeader>())
+            .expect("capacity overflow");
+        trace!("...and will end at {} ({:x})", after_header, after_header);
 
         //With the index to the unaligned memory address, determine the index to
         //the aligned memory address where the object will reside,
         //according to its memory alignment.
-        let start = utils::round_up(after_type_description, align);
+        let start = utils::round_up(after_header, align).expect("capacity overflow");
         trace!("The memory location for the actual data will begin at byte {} ({:x})...", start, start);
 
-        //Determine the index of the next aligned memory address for a type description, according to the size of the object
-        //and the memory alignment of a type description.
-        let end = utils::round_up(
-            start + n_bytes,
-            mem::align_of::<*const utils::TypeDescription>(),
-        );
+        //Determine the index of the footer, according to the size of the object
+        //and the memory alignment of a footer.
+        let footer_start = start
+            .checked_add(n_bytes)
+            .and_then(|end| utils::round_up(end, mem::align_of::<utils::AllocationFooter>()))
+            .expect("capacity overflow");
+        trace!("The memory location for the footer will begin at byte {} ({:x})...", footer_start, footer_start);
+
+        //The next header lies just after the footer, they have the same alignment.
+        let end = footer_start
+            .checked_add(mem::size_of::<utils::AllocationFooter>())
+            .expect("capacity overflow");
         trace!("...and will end at {} ({:x})", end, end);
 
         //Update the current top of the stack.
@@ -689,24 +1091,35 @@ impl StackAllocator {
         trace!("Setting the first unused byte of memory of the memory chunk to byte {} ({:x})", end, end);
         non_copy_storage.set_fill(end);
 
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites.record(Location::caller(), header_start, end);
+
         unsafe {
             // Get a raw pointer to the start of our MemoryChunk's RawVec
             let start_storage = non_copy_storage.as_ptr();
             trace!("Getting a raw pointer to the start of the allocation of the memory chunk: {:p}.", start_storage);
 
-            trace!("Returning a tuple of raw pointers to memory locations for the TypeDescription and data.");
+            //Write the footer, linking the end of the allocation to its header.
+            trace!("Writing the footer, holding the index of the header.");
+            ptr::write(
+                start_storage.offset(footer_start as isize) as *mut utils::AllocationFooter,
+                utils::AllocationFooter { header: header_start },
+            );
+
+            trace!("Returning a tuple of raw pointers to memory locations for the header and data.");
             (
                 //From this raw pointer, get the correct raw pointers with
                 //the indices we calculated earlier.
 
-                //The raw pointer to the type description of the object.
-                start_storage.offset(type_description_start as isize),
+                //The raw pointer to the header of the object.
+                start_storage.offset(header_start as isize),
                 //The raw pointer to the object.
                 start_storage.offset(start as isize),
             )
         }
     }
 
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     fn alloc_copy_inner(&self, n_bytes: usize, align: usize) -> AllocationResult<*const u8> {
         trace!("The copyable data has a size of {} bytes and an alignment of {} bytes.", n_bytes, align);
         //borrow mutably the memory chunk used by the allocator.
@@ -717,27 +1130,41 @@ impl StackAllocator {
         trace!("Getting the index of the first unused byte in the memory chunk.");
         let fill = copy_storage.fill();
 
+        //The error returned if one of the indices below can't be represented.
+        let overflow = AllocationError::CapacityOverflowError {
+            requested_size: n_bytes,
+            alignment: align,
+            storage: StorageKind::Copy,
+        };
+
         //Get the index of the aligned memory address, which will be returned.
-        let start = utils::round_up(fill, align);
+        let start = utils::round_up(fill, align).ok_or(overflow)?;
         trace!("The memory location for the actual data will begin at byte {} ({:x})...", start, start);
 
         //Get the index of the future first unused memory address, according to the size of the object.
-        let end = start + n_bytes;
+        let end = start.checked_add(n_bytes).ok_or(overflow)?;
         trace!("...and will end at {} ({:x})", end, end);
 
         //We don't grow the capacity, or create another chunk.
         trace!("Checking if the allocator has enough remaining memory to store the data.");
         if end >= copy_storage.capacity() {
             error!("The allocator doesn't have enough remaining memory to store the data !");
-            return Err(AllocationError::OutOfMemoryError(format!(
-                "The copy stack allocator is out of memory !"
-            )));
+            return Err(AllocationError::OutOfMemoryError {
+                requested_size: n_bytes,
+                alignment: align,
+                storage: StorageKind::Copy,
+                fill,
+                capacity: copy_storage.capacity(),
+            });
         }
 
         //Set the first unused memory address of the memory chunk to the index calculated earlier.
         trace!("Setting the first unused byte of memory of the memory chunk to byte {} ({:x})", end, end);
         copy_storage.set_fill(end);
 
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites_copy.record(Location::caller(), fill, end);
+
         trace!("Returning a raw pointer to a memory location for the data.");
         unsafe {
             //Return the raw pointer to the aligned memory location, which will be used to place
@@ -746,6 +1173,7 @@ impl StackAllocator {
         }
     }
 
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
     fn alloc_copy_inner_unchecked(&self, n_bytes: usize, align: usize) -> *const u8 {
         trace!("The copyable data has a size of {} bytes and an alignment of {} bytes (unchecked).", n_bytes, align);
         //borrow mutably the memory chunk used by the allocator.
@@ -757,17 +1185,20 @@ impl StackAllocator {
         let fill = copy_storage.fill();
 
         //Get the index of the aligned memory address, which will be returned.
-        let start = utils::round_up(fill, align);
+        let start = utils::round_up(fill, align).expect("capacity overflow");
         trace!("The memory location for the actual data will begin at byte {} ({:x})...", start, start);
 
         //Get the index of the future first unused memory address, according to the size of the object.
-        let end = start + n_bytes;
+        let end = start.checked_add(n_bytes).expect("capacity overflow");
         trace!("...and will end at {} ({:x})", end, end);
 
         //Set the first unused memory address of the memory chunk to the index calculated earlier.
         trace!("Setting the first unused byte of memory of the memory chunk to byte {} ({:x})", end, end);
         copy_storage.set_fill(end);
 
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites_copy.record(Location::caller(), fill, end);
+
         trace!("Returning a raw pointer to a memory location for the data.");
         unsafe {
             //Return the raw pointer to the aligned memory location, which will be used to place
@@ -844,6 +1275,14 @@ impl StackAllocator {
 
     /// Reset the memory storage storing data implementing the `Drop` trait, dropping all the content residing inside it.
     ///
+    /// The objects are dropped in the reverse order of their allocation, the most recent first.
+    ///
+    /// # Panic
+    /// If a destructor panics, the remaining objects are still dropped and the memory storage is reset.
+    /// The panic of the first panicking destructor is then resumed.
+    ///
+    /// This function will also panic if it is called by the destructor of an object being dropped by a reset.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -872,16 +1311,33 @@ impl StackAllocator {
     /// ```
     pub fn reset(&self) {
         debug!("Resetting completely the memory chunk holding droppable data.");
-        unsafe {
+        if self.storage.borrow().is_destroying() {
+            panic!("The memory storage holding droppable data cannot be reset while its objects are being dropped.");
+        }
+
+        let destroyed = unsafe {
             trace!("all data is being dropped.");
-            self.storage.borrow().destroy();
-            trace!("the first unused byte of memory is being set to 0.");
-            self.storage.borrow().set_fill(0);
+            self.storage.borrow().destroy()
+        };
+        trace!("the first unused byte of memory is being set to 0.");
+        self.storage.borrow().set_fill(0);
+        self.invalidate_handles();
+
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites.clear();
+
+        //The memory storage is consistent, the panic of a destructor can be resumed.
+        if let Err(payload) = destroyed {
+            panic::resume_unwind(payload);
         }
     }
 
     /// Reset the memory storage storing data implementing the `Drop` trait, dropping all the content residing inside it.
     ///
+    /// # Panic
+    /// This function will panic if a child allocator or a sub-allocator, created by `split_off()` or `partition()`,
+    /// has not been dropped yet.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -910,21 +1366,54 @@ impl StackAllocator {
     /// ```
     pub fn reset_copy(&self) {
         debug!("Resetting completely the memory chunk holding copyable data.");
+        //The memory of the child allocators lies in the memory storage holding copyable data.
+        let lent = {
+            let mut children = lock_children(&self.children);
+            if children.iter().any(|child| child.objects.is_none()) {
+                panic!("The memory storage holding copyable data cannot be reset while a child allocator is alive.");
+            }
+            mem::take(&mut *children)
+        };
+
+        let destroyed = unsafe {
+            trace!("The data left by the sub-allocators is being dropped.");
+            destroy_lent_memory(lent)
+        };
         trace!("the first unused byte of memory is being set to 0.");
         self.storage_copy.borrow().set_fill(0);
+        self.invalidate_handles();
+
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites_copy.clear();
+
+        if let Err(payload) = destroyed {
+            panic::resume_unwind(payload);
+        }
     }
 
     /// Reset partially the memory storage storing data implementing the `Drop` trait, dropping all the content residing between the marker and
     /// the first unused memory address of the memory storage.
     ///
+    /// The objects are dropped in the reverse order of their allocation, the most recent first.
+    ///
+    /// # Panic
+    /// If a destructor panics, the remaining objects are still dropped and the memory storage is reset to the marker.
+    /// The panic of the first panicking destructor is then resumed.
+    ///
+    /// # Error
+    /// This function will return an error if the marker lies after the first unused memory address, or is not the location of
+    /// an object's header.
+    ///
+    /// It will also return an error if it is called by the destructor of an object being dropped by a reset.
+    ///
     /// # Example
     ///
     /// ```rust
     /// use maskerad_memory_allocators::StackAllocator;
     /// # use std::error::Error;
     /// # fn try_main() -> Result<(), Box<Error>> {
-    /// // 100 bytes for data implementing Drop, 100 bytes for Data implementing Copy.
-    /// let allocator = StackAllocator::with_capacity(100, 100);
+    /// // 200 bytes for data implementing Drop, 100 bytes for Data implementing Copy.
+    /// let allocator = StackAllocator::with_capacity(200, 100);
     ///
     /// //When nothing has been allocated, the first unused memory address is at index 0.
     /// assert_eq!(allocator.marker(), 0);
@@ -943,7 +1432,7 @@ impl StackAllocator {
     ///
     /// assert_ne!(allocator.marker(), index_current_top);
     ///
-    /// allocator.reset_to_marker(index_current_top);
+    /// allocator.reset_to_marker(index_current_top)?;
     ///
     /// //The memorychunk storing data implementing the Drop trait has been partially reset, and all the content lying between the marker and
     /// //the first unused memory address has been dropped.
@@ -955,18 +1444,57 @@ impl StackAllocator {
     /// #   try_main().unwrap();
     /// # }
     /// ```
-    pub fn reset_to_marker(&self, marker: usize) {
+    pub fn reset_to_marker(&self, marker: usize) -> AllocationResult<()> {
         debug!("Resetting partially the memory chunk holding droppable data to the marker {}.", marker);
-        unsafe {
-            trace!("The data lying between the byte {} and the byte {} is being dropped.", marker, self.storage.borrow().fill());
-            self.storage.borrow().destroy_to_marker(marker);
-            trace!("The first unused byte of memory is being set to {}", marker);
-            self.storage.borrow().set_fill(marker);
+        //The memory chunk cannot be reset by the destructors of its objects.
+        if self.storage.borrow().is_destroying() {
+            error!("The memory chunk is already dropping its objects !");
+            return Err(AllocationError::ResetInProgressError {
+                storage: StorageKind::Drop,
+            });
+        }
+
+        //A marker must lie below the top of the stack, on the location of a header.
+        trace!("Checking if the marker is a valid location in the memory chunk.");
+        let fill = self.storage.borrow().fill();
+        if !unsafe { self.storage.borrow().is_allocation_boundary(marker) } {
+            error!("The marker {} is not a valid location in the memory chunk !", marker);
+            return Err(AllocationError::InvalidMarkerError {
+                marker,
+                fill,
+                storage: StorageKind::Drop,
+            });
+        }
+
+        let destroyed = unsafe {
+            trace!("The data lying between the byte {} and the byte {} is being dropped.", marker, fill);
+            self.storage.borrow().destroy_to_marker(marker)
+        };
+        trace!("The first unused byte of memory is being set to {}", marker);
+        self.storage.borrow().set_fill(marker);
+        self.invalidate_handles();
+
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites.truncate(marker);
+
+        //The memory storage is consistent, the panic of a destructor can be resumed.
+        if let Err(payload) = destroyed {
+            panic::resume_unwind(payload);
         }
+
+        Ok(())
     }
 
     /// Reset partially the memory storage storing data implementing the `Copy` trait.
     ///
+    /// # Error
+    /// This function will return an error if the marker lies after the first unused memory address,
+    /// or below the end of the memory lent to a child allocator or a sub-allocator which has not been dropped yet.
+    ///
+    /// # Panic
+    /// The data left by the sub-allocators above the marker is dropped. If a destructor panics, the remaining objects
+    /// are still dropped and the memory storage is reset. The panic of the first panicking destructor is then resumed.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -993,7 +1521,7 @@ impl StackAllocator {
     ///
     /// assert_ne!(allocator.marker_copy(), index_current_top);
     ///
-    /// allocator.reset_to_marker_copy(index_current_top);
+    /// allocator.reset_to_marker_copy(index_current_top)?;
     ///
     /// //The memorychunk storing data implementing the Copy trait has been partially reset.
     ///
@@ -1004,10 +1532,60 @@ impl StackAllocator {
     /// #   try_main().unwrap();
     /// # }
     /// ```
-    pub fn reset_to_marker_copy(&self, marker: usize) {
+    pub fn reset_to_marker_copy(&self, marker: usize) -> AllocationResult<()> {
         debug!("Resetting partially the memory chunk holding copyable data to the marker {}.", marker);
+        //A marker must lie below the top of the stack.
+        trace!("Checking if the marker is a valid location in the memory chunk.");
+        let fill = self.storage_copy.borrow().fill();
+        if marker > fill {
+            error!("The marker {} is not a valid location in the memory chunk !", marker);
+            return Err(AllocationError::InvalidMarkerError {
+                marker,
+                fill,
+                storage: StorageKind::Copy,
+            });
+        }
+
+        let lent = {
+            let mut children = lock_children(&self.children);
+
+            //The memory lent to the child allocators cannot be given back before they are dropped.
+            let alive = children
+                .iter()
+                .filter(|child| child.objects.is_none())
+                .map(|child| child.end)
+                .max();
+            if let Some(child_end) = alive {
+                if marker < child_end {
+                    error!("The marker {} lies in the memory of a child allocator !", marker);
+                    return Err(AllocationError::ChildInUseError { marker, child_end });
+                }
+            }
+
+            //The data left by the sub-allocators above the marker must be dropped.
+            let (given_back, kept): (Vec<LentMemory>, Vec<LentMemory>) = mem::take(&mut *children)
+                .into_iter()
+                .partition(|child| child.end > marker);
+            *children = kept;
+            given_back
+        };
+
+        let destroyed = unsafe {
+            trace!("The data left by the sub-allocators above the marker is being dropped.");
+            destroy_lent_memory(lent)
+        };
         trace!("The first unused byte of memory is being set to {}", marker);
         self.storage_copy.borrow().set_fill(marker);
+        self.invalidate_handles();
+
+        #[cfg(feature = "allocation_tracking")]
+        self.call_sites_copy.truncate(marker);
+
+        if let Err(payload) = destroyed {
+            panic::resume_unwind(payload);
+        }
+
+        Ok(())
     }
 
     /// Returns the maximum capacity the memory storage storing data implementing the `Drop` trait can hold.
@@ -1040,133 +1618,812 @@ impl StackAllocator {
         self.storage_copy.borrow().capacity()
     }
 
-    /// Returns a raw pointer to the start of the memory storage storing data implementing the `Drop` trait.
+    /// Returns the memory used by the allocator.
     ///
     /// # Example
     ///
     /// ```rust
     /// use maskerad_memory_allocators::StackAllocator;
-    /// // 100 bytes for data implementing Drop, 100 bytes for Data implementing Copy.
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
     /// let allocator = StackAllocator::with_capacity(100, 50);
-    /// let ptr = allocator.storage_as_ptr();
+    /// allocator.alloc(|| 26 as u8)?;
+    ///
+    /// let statistics = allocator.statistics();
+    /// assert_eq!(statistics.used, 0);
+    /// assert_eq!(statistics.capacity, 100);
+    /// assert_eq!(statistics.used_copy, allocator.marker_copy());
+    /// assert_eq!(statistics.capacity_copy, 50);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
     /// ```
-    pub fn storage_as_ptr(&self) -> *const u8 {
-        debug!("Getting a raw pointer to the start of the allocation of the memory chunk storing droppable data.");
-        self.storage.borrow().as_ptr()
+    pub fn statistics(&self) -> AllocatorStatistics {
+        debug!("Getting the memory used by the stack allocator.");
+        //A frozen allocator gives its statistics to several threads at once, the memory chunks must not be borrowed.
+        let (storage, storage_copy) = (self.chunk(StorageKind::Drop), self.chunk(StorageKind::Copy));
+        AllocatorStatistics {
+            used: storage.fill(),
+            capacity: storage.capacity(),
+            used_copy: storage_copy.fill(),
+            capacity_copy: storage_copy.capacity(),
+        }
     }
 
-    /// Returns a raw pointer to the start of the memory storage storing data implementing the `Copy` trait.
+    /// Creates a handle to data allocated by the allocator.
+    ///
+    /// Unlike a reference, a handle doesn't borrow the allocator: it can be kept while the allocator is frozen,
+    /// and resolved by the frozen view. Any reset of the allocator invalidates its handles.
+    ///
+    /// Returns `None` if the data doesn't lie in the memory used by the allocator, or lies in the memory lent
+    /// to a child allocator.
     ///
     /// # Example
     ///
     /// ```rust
     /// use maskerad_memory_allocators::StackAllocator;
-    /// // 100 bytes for data implementing Drop, 100 bytes for Data implementing Copy.
-    /// let allocator = StackAllocator::with_capacity(100, 50);
-    /// let ptr = allocator.storage_copy_as_ptr();
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 100);
+    /// let handle = {
+    ///     let my_string = allocator.alloc(|| String::from("render data"))?;
+    ///     allocator.handle(my_string).unwrap()
+    /// };
+    ///
+    /// assert_eq!(allocator.get(handle).unwrap(), "render data");
+    ///
+    /// // The handle is stale after a reset.
+    /// allocator.reset();
+    /// assert!(allocator.get(handle).is_none());
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
     /// ```
-    pub fn storage_copy_as_ptr(&self) -> *const u8 {
-        debug!("Getting a raw pointer to the start of the allocation of the memory chunk storing copyable data.");
-        self.storage_copy.borrow().as_ptr()
-    }
+    pub fn handle<T>(&self, data: &T) -> Option<StackHandle<T>> {
+        debug!("Creating a handle to data allocated by the stack allocator.");
+        let address = data as *const T as usize;
+        let (start, end) = (address, address + mem::size_of::<T>());
+
+        for &storage in [StorageKind::Drop, StorageKind::Copy].iter() {
+            let chunk = self.chunk(storage);
+            let base = chunk.as_ptr() as usize;
+            if start < base || end > base + chunk.fill() {
+                continue;
+            }
 
-    /// Drop all the objects implementing the `Drop` trait.
-    fn destroy_stack(&self) -> Result<(), BorrowError> {
-        debug!("The StackAllocator is being dropped, all droppable data is being dropped.");
-        unsafe {
-            self.storage.try_borrow()?.destroy();
+            //The child allocators reuse their memory without the parent knowing it.
+            let offset = start - base;
+            if storage == StorageKind::Copy && lock_children(&self.children)
+                .iter()
+                .any(|child| offset < child.end && offset + mem::size_of::<T>() > child.start)
+            {
+                error!("The data lies in the memory lent to a child allocator !");
+                return None;
+            }
+
+            trace!("The data lies at byte {} of the {}.", offset, storage);
+            return Some(StackHandle {
+                allocator: self.id,
+                generation: self.generation.get(),
+                storage,
+                offset,
+                _phantom: PhantomData,
+            });
         }
-        Ok(())
-    }
-}
 
-impl Drop for StackAllocator {
-    fn drop(&mut self) {
-        self.destroy_stack().unwrap();
+        error!("The data doesn't lie in the memory of the stack allocator !");
+        None
     }
-}
 
-#[cfg(test)]
-mod stack_allocator_test {
-    use super::*;
-
-    //size : 4 bytes + 4 bytes alignment + 4 bytes + 4 bytes alignment + alignment-offset stuff -> ~16-20 bytes.
-    struct Monster {
-        _hp: u32,
+    /// Returns a reference to the data designated by the handle.
+    ///
+    /// Returns `None` if the handle has been created by another allocator, or if the allocator has been reset since its creation.
+    pub fn get<T>(&self, handle: StackHandle<T>) -> Option<&T> {
+        debug!("Resolving a handle to data allocated by the stack allocator.");
+        self.resolve(handle).map(|data| unsafe { &*data })
     }
 
-    impl Monster {
-        pub fn new(hp: u32) -> Self {
-            Monster { _hp: hp }
-        }
+    /// Freezes the allocator into a read-only view, which can be shared between threads.
+    ///
+    /// See `FrozenStack`.
+    pub fn freeze(self) -> FrozenStack {
+        debug!("Freezing the stack allocator.");
+        FrozenStack::new(self)
     }
 
-    impl Default for Monster {
-        fn default() -> Self {
-            Monster { _hp: 1 }
-        }
+    /// Returns the identifier of the allocator, unique in the process.
+    pub(crate) fn id(&self) -> usize {
+        self.id
     }
 
-    impl Drop for Monster {
-        fn drop(&mut self) {
-            println!("I'm dying !");
+    /// Returns a raw pointer to the data designated by the handle, if the handle is still valid.
+    ///
+    /// The memory chunks are not borrowed: a frozen allocator resolves handles from several threads at once.
+    pub(crate) fn resolve<T>(&self, handle: StackHandle<T>) -> Option<*const T> {
+        if handle.allocator != self.id || handle.generation != self.generation.get() {
+            debug!("The handle has been created by another allocator, or before a reset.");
+            return None;
         }
-    }
-
-    #[test]
-    fn creation_with_right_capacity() {
-        unsafe {
-            //create a StackAllocator with the specified size.
-            let alloc = StackAllocator::with_capacity(200, 200);
-            let start_chunk = alloc.storage_as_ptr();
-            let first_unused_mem_addr = start_chunk.offset(alloc.marker() as isize);
 
-            assert_eq!(start_chunk, first_unused_mem_addr);
+        let chunk = self.chunk(handle.storage);
+        if handle.offset + mem::size_of::<T>() > chunk.fill() {
+            return None;
         }
-    }
 
-    #[test]
-    fn allocation_test() {
-        //We allocate 200 bytes of memory.
-        let alloc = StackAllocator::with_capacity(200, 200);
-
-        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
-
-        unsafe {
-            let start_alloc = alloc.storage_as_ptr();
-            let top_stack_index = alloc.marker();
-            let top_stack = start_alloc.offset(top_stack_index as isize);
-            assert_ne!(start_alloc, top_stack);
-        }
+        unsafe { Some(chunk.as_ptr().offset(handle.offset as isize) as *const T) }
     }
 
-    //Use 'cargo test -- --nocapture' to see the monsters' println!s
-    #[test]
-    fn test_reset() {
-        let alloc = StackAllocator::with_capacity(200, 200);
-        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
-
-        let top_stack_index = alloc.marker();
-        let start_alloc = alloc.storage_as_ptr();
-        let mut current_top_stack_index = alloc.marker();
-
+    /// Returns the memory chunk of the given memory storage, without updating the borrow flag of its RefCell.
+    ///
+    /// The memory chunks are only borrowed mutably when a sub-allocator gives its objects to its parent, nobody can read
+    /// them at this moment.
+    fn chunk(&self, storage: StorageKind) -> &MemoryChunk {
         unsafe {
-            let top_stack = start_alloc.offset(top_stack_index as isize);
-            let current_top_stack = start_alloc.offset(current_top_stack_index as isize);
-            assert_eq!(current_top_stack, top_stack);
+            match storage {
+                StorageKind::Drop => &*self.storage.as_ptr(),
+                StorageKind::Copy => &*self.storage_copy.as_ptr(),
+            }
         }
+    }
 
-        let _another_monster = alloc.alloc(|| Monster::default()).unwrap();
+    /// Makes the handles to the data of the allocator stale, after a reset.
+    fn invalidate_handles(&self) {
+        trace!("The handles created before the reset are now stale.");
+        self.generation.set(self.generation.get().wrapping_add(1));
+    }
 
-        current_top_stack_index = alloc.marker();
+    /// Lends memory at the top of the memory storage storing data implementing the `Copy` trait to a new allocator,
+    /// with the given capacities in bytes.
+    ///
+    /// The child allocator is independent from its parent: resetting it never drops the data of the parent.
+    /// The parent can still allocate data above the lent memory, but cannot be reset below it until the child is dropped.
+    /// The objects of the child are dropped with it.
+    ///
+    /// The memory storages of the child are aligned on 16 bytes.
+    ///
+    /// # Error
+    /// This function will return an error if the memory storage storing data implementing the `Copy` trait
+    /// doesn't have enough remaining memory for both memory storages of the child.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let frame_allocator = StackAllocator::with_capacity(100, 1000);
+    ///
+    /// {
+    ///     // The physics get their own budget.
+    ///     let physics = frame_allocator.split_off(200, 200)?;
+    ///     let contacts = physics.alloc(|| vec![1, 2, 3])?;
+    ///     assert_eq!(contacts.len(), 3);
+    ///
+    ///     // The parent cannot be reset below the memory of the child.
+    ///     assert!(frame_allocator.reset_to_marker_copy(0).is_err());
+    ///
+    ///     physics.reset();
+    /// }
+    ///
+    /// // The child is dropped, its memory can be given back.
+    /// frame_allocator.reset_to_marker_copy(0)?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn split_off(&self, capacity: usize, capacity_copy: usize) -> AllocationResult<ChildAllocator> {
+        debug!("Lending {} bytes for droppable data and {} bytes for copyable data to a child allocator.", capacity, capacity_copy);
+        let (allocator, end) = self.lend(capacity, capacity_copy)?;
+        Ok(ChildAllocator {
+            allocator,
+            parent: self,
+            end,
+        })
+    }
 
-        unsafe {
+    /// Lends the remaining memory of the memory storage storing data implementing the `Copy` trait to `count`
+    /// sub-allocators, which can be sent to other threads.
+    ///
+    /// Every sub-allocator gets an equal share of the remaining memory: half of it for data implementing the `Drop` trait,
+    /// half of it for data implementing the `Copy` trait. The memory storages of a sub-allocator are aligned on 16 bytes.
+    ///
+    /// The data allocated by a sub-allocator stays readable after the sub-allocator is dropped, until the lent memory
+    /// is given back by `reset_copy()` or `reset_to_marker_copy()`. The objects are dropped by the parent at this moment.
+    /// The parent cannot be reset below the lent memory while a sub-allocator is alive.
+    ///
+    /// # Error
+    /// This function will return an error if the remaining memory is too small to be shared between `count` sub-allocators.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// use std::thread;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let frame_allocator = StackAllocator::with_capacity(100, 10000);
+    /// let workers = frame_allocator.partition(4)?;
+    ///
+    /// // Every worker generates a mesh in its own memory.
+    /// let meshes: Vec<&Vec<f32>> = thread::scope(|scope| {
+    ///     let jobs: Vec<_> = workers.into_iter().enumerate().map(|(i, worker)| {
+    ///         scope.spawn(move || worker.alloc(|| vec![i as f32; 3]).unwrap())
+    ///     }).collect();
+    ///
+    ///     jobs.into_iter().map(|job| job.join().unwrap()).collect()
+    /// });
+    ///
+    /// // The meshes are still readable from the parent...
+    /// assert_eq!(meshes[2], &vec![2.0; 3]);
+    ///
+    /// // ...until its next reset.
+    /// frame_allocator.reset_copy();
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn partition(&self, count: usize) -> AllocationResult<Vec<SubAllocator>> {
+        debug!("Lending the remaining memory of the stack allocator to {} sub-allocators.", count);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let start = self.marker_copy();
+        let remaining = self.capacity_copy().saturating_sub(start + 1);
+        //Both memory storages of a sub-allocator may need up to CHILD_ALIGNMENT bytes of padding.
+        let capacity = (remaining / count / 2).saturating_sub(CHILD_ALIGNMENT);
+        trace!("Every sub-allocator gets {} bytes for each of its memory storages.", capacity);
+
+        let mut sub_allocators = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.lend(capacity, capacity) {
+                Ok((allocator, end)) => sub_allocators.push(SubAllocator {
+                    allocator,
+                    children: &self.children,
+                    end,
+                }),
+                Err(error) => {
+                    //The sub-allocators already created hold no data, their memory can be given back.
+                    drop(sub_allocators);
+                    self.reset_to_marker_copy(start)?;
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(sub_allocators)
+    }
+
+    /// Reserves the memory of both memory storages of a child allocator at the top of the memory storage storing data
+    /// implementing the `Copy` trait.
+    ///
+    /// Returns the child, and the index of the first byte after the lent memory.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    fn lend(&self, capacity: usize, capacity_copy: usize) -> AllocationResult<(StackAllocator, usize)> {
+        let start = self.marker_copy();
+        let storage = self.alloc_copy_inner(capacity, CHILD_ALIGNMENT)?;
+        let storage_copy = match self.alloc_copy_inner(capacity_copy, CHILD_ALIGNMENT) {
+            Ok(storage_copy) => storage_copy,
+            Err(error) => {
+                trace!("Giving back the memory reserved for the child allocator.");
+                self.storage_copy.borrow().set_fill(start);
+                #[cfg(feature = "allocation_tracking")]
+                self.call_sites_copy.truncate(start);
+                return Err(error);
+            }
+        };
+
+        let end = self.marker_copy();
+        trace!("The child allocator uses the copyable data storage between the bytes {} and {}.", start, end);
+        lock_children(&self.children).push(LentMemory { start, end, objects: None });
+
+        let child = StackAllocator {
+            //The parent will not give this memory to anyone else until the child is dropped.
+            storage: RefCell::new(unsafe { MemoryChunk::from_raw_parts(storage as *mut u8, capacity) }),
+            storage_copy: RefCell::new(unsafe { MemoryChunk::from_raw_parts(storage_copy as *mut u8, capacity_copy) }),
+            children: Mutex::new(Vec::new()),
+            id: NEXT_ALLOCATOR_ID.fetch_add(1, Ordering::Relaxed),
+            generation: Cell::new(0),
+            #[cfg(feature = "allocation_tracking")]
+            call_sites: CallSiteTracker::new(),
+            #[cfg(feature = "allocation_tracking")]
+            call_sites_copy: CallSiteTracker::new(),
+        };
+
+        Ok((child, end))
+    }
+
+    /// Returns a raw pointer to the start of the memory storage storing data implementing the `Drop` trait.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// // 100 bytes for data implementing Drop, 100 bytes for Data implementing Copy.
+    /// let allocator = StackAllocator::with_capacity(100, 50);
+    /// let ptr = allocator.storage_as_ptr();
+    /// ```
+    pub fn storage_as_ptr(&self) -> *const u8 {
+        debug!("Getting a raw pointer to the start of the allocation of the memory chunk storing droppable data.");
+        self.storage.borrow().as_ptr()
+    }
+
+    /// Returns a raw pointer to the start of the memory storage storing data implementing the `Copy` trait.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// // 100 bytes for data implementing Drop, 100 bytes for Data implementing Copy.
+    /// let allocator = StackAllocator::with_capacity(100, 50);
+    /// let ptr = allocator.storage_copy_as_ptr();
+    /// ```
+    pub fn storage_copy_as_ptr(&self) -> *const u8 {
+        debug!("Getting a raw pointer to the start of the allocation of the memory chunk storing copyable data.");
+        self.storage_copy.borrow().as_ptr()
+    }
+
+    /// Returns an iterator over the objects living in the memory storage storing data implementing the `Drop` trait,
+    /// from the bottom of the stack to its top.
+    ///
+    /// For every object, the iterator yields its index, its size, its alignment, whether or not it has been initialized
+    /// and, in debug builds, the name of its type.
+    ///
+    /// The iterator holds a shared borrow of the memory storage until it is dropped, and only walks the objects living
+    /// when it was created: the data allocated while iterating is not yielded. The memory storage should not be reset
+    /// while iterating, the iterator would describe objects which have already been dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(200, 100);
+    ///
+    /// let my_vec: &Vec<u8> = allocator.alloc(|| {
+    ///     Vec::with_capacity(10)
+    /// })?;
+    /// let my_string: &String = allocator.alloc(|| {
+    ///     String::from("hello")
+    /// })?;
+    ///
+    /// let allocations: Vec<_> = allocator.allocations().collect();
+    /// assert_eq!(allocations.len(), 2);
+    ///
+    /// assert_eq!(allocations[0].offset, my_vec as *const Vec<u8> as usize - allocator.storage_as_ptr() as usize);
+    /// assert!(allocations[1].is_initialized);
+    /// assert_eq!(allocations[1].end, allocator.marker());
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn allocations(&self) -> Allocations {
+        debug!("Getting an iterator over the data contained in the memory chunk storing droppable data.");
+        Allocations::new(self.storage.borrow())
+    }
+
+    /// Renders the memory map of both memory storages in the given format.
+    ///
+    /// The memory map shows, for the memory storage storing data implementing the `Drop` trait, the headers, the objects
+    /// and the padding between them. For the memory storage storing data implementing the `Copy` trait, only the used bytes
+    /// are shown, since the boundaries of the objects are not recorded. The free space and the marker of both memory storages
+    /// are shown too.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::{StackAllocator, DumpFormat};
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 100);
+    ///
+    /// let my_vec: &Vec<u8> = allocator.alloc(|| {
+    ///     Vec::with_capacity(10)
+    /// })?;
+    ///
+    /// let text = allocator.dump(DumpFormat::Text);
+    /// assert!(text.contains("header"));
+    ///
+    /// let json = allocator.dump(DumpFormat::Json);
+    /// assert!(json.starts_with("{\"storages\":["));
+    ///
+    /// let svg = allocator.dump(DumpFormat::Svg);
+    /// assert!(svg.starts_with("<svg"));
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn dump(&self, format: DumpFormat) -> String {
+        debug!("Dumping the memory map of the StackAllocator.");
+        let layouts = [
+            StorageLayout::from_allocations("drop", self.capacity(), self.marker(), self.allocations()),
+            StorageLayout::from_marker("copy", self.capacity_copy(), self.marker_copy()),
+        ];
+        memory_dump::render(&layouts, format)
+    }
+
+    /// Returns a report of the live allocations of both memory storages, aggregated by the source location
+    /// which asked for them.
+    ///
+    /// The bytes used by an allocation include its header and its alignment padding. This report can be used
+    /// to find which part of a program used most of the memory, when an allocation fails.
+    ///
+    /// This function is only available with the `allocation_tracking` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::StackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = StackAllocator::with_capacity(100, 100);
+    ///
+    /// for i in 0..3 {
+    ///     allocator.alloc(|| i as u64)?;
+    /// }
+    ///
+    /// let report = allocator.report();
+    /// assert_eq!(report.call_sites().len(), 1);
+    /// assert_eq!(report.call_sites()[0].count, 3);
+    /// assert_eq!(report.total_bytes(), allocator.marker_copy());
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "allocation_tracking")]
+    pub fn report(&self) -> AllocationReport {
+        debug!("Creating a report of the live allocations, by call site.");
+        AllocationReport::from_trackers(&[&self.call_sites, &self.call_sites_copy])
+    }
+
+    /// Drop all the objects implementing the `Drop` trait, including the data left by the sub-allocators.
+    ///
+    /// Returns the payload of the first panicking destructor, if any.
+    fn destroy_stack(&mut self) -> thread::Result<()> {
+        debug!("The StackAllocator is being dropped, all droppable data is being dropped.");
+        let lent = mem::take(&mut *lock_children(&self.children));
+        let destroyed_lent = unsafe { destroy_lent_memory(lent) };
+        let destroyed = unsafe { self.storage.get_mut().destroy() };
+        destroyed_lent.and(destroyed)
+    }
+}
+
+/// Extracts the value of a result whose error cannot exist.
+fn unwrap_infallible<T>(result: Result<T, Infallible>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
+
+/// The alignment of the memory storages of a child allocator.
+///
+/// The allocators align their data relatively to the start of their memory storages, which must be aligned as well.
+const CHILD_ALIGNMENT: usize = 16;
+
+/// An allocator using memory lent by a parent `StackAllocator`, created by `StackAllocator::split_off()`.
+///
+/// It can be used as a `StackAllocator`. When it is dropped, its objects are dropped and the parent can be reset
+/// below the lent memory again.
+#[derive(Debug)]
+pub struct ChildAllocator<'a> {
+    allocator: StackAllocator,
+    parent: &'a StackAllocator,
+    /// The index of the first byte after the lent memory, in the memory storage of the parent holding copyable data.
+    end: usize,
+}
+
+impl<'a> Deref for ChildAllocator<'a> {
+    type Target = StackAllocator;
+
+    fn deref(&self) -> &StackAllocator {
+        &self.allocator
+    }
+}
+
+impl<'a> Drop for ChildAllocator<'a> {
+    fn drop(&mut self) {
+        debug!("Dropping a child allocator, its memory is given back to its parent.");
+        //The objects must be dropped while the parent still protects their memory.
+        let destroyed = panic::catch_unwind(AssertUnwindSafe(|| self.allocator.reset()));
+
+        {
+            let mut children = lock_children(&self.parent.children);
+            if let Some(index) = children.iter().position(|child| child.end == self.end) {
+                children.swap_remove(index);
+            }
+        }
+
+        if let Err(payload) = destroyed {
+            //Resuming a panic while the thread is already unwinding would abort the process.
+            if thread::panicking() {
+                error!("A destructor panicked while the child allocator was dropped during a panic, the panic is discarded !");
+            } else {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+/// An allocator using memory lent by a parent `StackAllocator`, created by `StackAllocator::partition()`.
+///
+/// It can be sent to another thread, a scoped thread for example. The allocated data must implement the `Send` trait:
+/// it is dropped by the parent, when the lent memory is given back. Until then, the data stays readable even if the
+/// sub-allocator has been dropped.
+///
+/// A sub-allocator cannot be reset.
+#[derive(Debug)]
+pub struct SubAllocator<'a> {
+    allocator: StackAllocator,
+    /// The memory lent by the parent, where the sub-allocator leaves its objects when it is dropped.
+    children: &'a Mutex<Vec<LentMemory>>,
+    /// The index of the first byte after the lent memory, in the memory storage of the parent holding copyable data.
+    end: usize,
+}
+
+impl<'a> SubAllocator<'a> {
+    /// Allocates data in the sub-allocator's memory, returning a mutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc_mut()`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&'a mut T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in a sub-allocator, returning a mutable reference.");
+        //The data lives until the parent gives the memory back, not until the sub-allocator is dropped.
+        self.allocator.alloc_mut(op).map(|data| unsafe { &mut *(data as *mut T) })
+    }
+
+    /// Allocates data in the sub-allocator's memory, returning an immutable reference to the allocated data.
+    ///
+    /// See `StackAllocator::alloc()`.
+    #[cfg_attr(feature = "allocation_tracking", track_caller)]
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&'a T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in a sub-allocator, returning an immutable reference.");
+        //The data lives until the parent gives the memory back, not until the sub-allocator is dropped.
+        self.allocator.alloc(op).map(|data| unsafe { &*(data as *const T) })
+    }
+
+    /// Returns the index of the first unused memory address of the memory storage storing data implementing the `Drop` trait.
+    pub fn marker(&self) -> usize {
+        self.allocator.marker()
+    }
+
+    /// Returns the index of the first unused memory address of the memory storage storing data implementing the `Copy` trait.
+    pub fn marker_copy(&self) -> usize {
+        self.allocator.marker_copy()
+    }
+
+    /// Returns the memory used by the sub-allocator.
+    pub fn statistics(&self) -> AllocatorStatistics {
+        self.allocator.statistics()
+    }
+}
+
+impl<'a> Drop for SubAllocator<'a> {
+    fn drop(&mut self) {
+        debug!("Dropping a sub-allocator, its data is given to its parent.");
+        //The parent drops the objects when it gives the lent memory back.
+        let objects = mem::replace(&mut *self.allocator.storage.borrow_mut(), MemoryChunk::new(0));
+        let mut children = lock_children(self.children);
+        if let Some(child) = children.iter_mut().find(|child| child.end == self.end) {
+            child.objects = Some(objects);
+        }
+    }
+}
+
+/// The memory lent by an allocator to a child allocator or a sub-allocator, in its memory storage holding copyable data.
+#[derive(Debug)]
+struct LentMemory {
+    /// The index of the first byte of the lent memory.
+    start: usize,
+    /// The index of the first byte after the lent memory.
+    end: usize,
+    /// The objects left by a dropped sub-allocator. `None` while the child is alive.
+    objects: Option<MemoryChunk>,
+}
+
+/// Locks the memory lent by an allocator.
+///
+/// The lock is never held while a destructor runs: it cannot be poisoned by a panicking destructor.
+fn lock_children(children: &Mutex<Vec<LentMemory>>) -> MutexGuard<Vec<LentMemory>> {
+    children.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Drops the objects left by the sub-allocators in the given lent memory, from the top of the stack to its bottom.
+///
+/// See `MemoryChunk::destroy_to_marker()` for the behavior when a destructor panics.
+unsafe fn destroy_lent_memory(mut lent: Vec<LentMemory>) -> thread::Result<()> {
+    lent.sort_by_key(|child| Reverse(child.end));
+
+    let mut result = Ok(());
+    for objects in lent.iter().filter_map(|child| child.objects.as_ref()) {
+        let destroyed = objects.destroy();
+        if result.is_ok() {
+            result = destroyed;
+        }
+    }
+    result
+}
+
+/// Gives back the memory of an allocation to the allocator if the initializer of the allocated data panics
+/// or returns an error.
+///
+/// The memory is only given back if the allocation is still at the top of the stack. If the initializer allocated
+/// data in the same memory storage before failing, the allocation stays in the memory storage:
+/// the header of a droppable object is still marked as not initialized, the object will not be dropped.
+struct RollbackGuard<'a> {
+    allocator: &'a StackAllocator,
+    storage: StorageKind,
+    /// Index of the first byte used by the allocation.
+    start: usize,
+    /// Index of the first byte after the allocation.
+    end: usize,
+}
+
+impl<'a> RollbackGuard<'a> {
+    /// Creates a guard for the allocation lying between the given index and the current top of the stack.
+    fn new(allocator: &'a StackAllocator, storage: StorageKind, start: usize) -> Self {
+        let end = match storage {
+            StorageKind::Drop => allocator.storage.borrow().fill(),
+            StorageKind::Copy => allocator.storage_copy.borrow().fill(),
+        };
+
+        RollbackGuard {
+            allocator,
+            storage,
+            start,
+            end,
+        }
+    }
+
+    /// The data has been initialized, keep the allocation.
+    fn disarm(self) {
+        mem::forget(self);
+    }
+}
+
+impl<'a> Drop for RollbackGuard<'a> {
+    fn drop(&mut self) {
+        let chunk = match self.storage {
+            StorageKind::Drop => self.allocator.storage.borrow(),
+            StorageKind::Copy => self.allocator.storage_copy.borrow(),
+        };
+
+        if chunk.fill() != self.end {
+            debug!("The initializer failed after allocating in the {}, the allocation is kept.", self.storage);
+            return;
+        }
+
+        debug!("The initializer failed, rolling back the allocation in the {} to byte {}.", self.storage, self.start);
+        chunk.set_fill(self.start);
+
+        #[cfg(feature = "allocation_tracking")]
+        match self.storage {
+            StorageKind::Drop => self.allocator.call_sites.truncate(self.start),
+            StorageKind::Copy => self.allocator.call_sites_copy.truncate(self.start),
+        }
+    }
+}
+
+impl Drop for StackAllocator {
+    fn drop(&mut self) {
+        if let Err(payload) = self.destroy_stack() {
+            //Resuming a panic while the thread is already unwinding would abort the process.
+            if thread::panicking() {
+                error!("A destructor panicked while the stack allocator was dropped during a panic, the panic is discarded !");
+            } else {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod stack_allocator_test {
+    use super::*;
+    use std::usize;
+
+    //size : 4 bytes + 4 bytes alignment + 4 bytes + 4 bytes alignment + alignment-offset stuff -> ~16-20 bytes.
+    struct Monster {
+        _hp: u32,
+    }
+
+    impl Monster {
+        pub fn new(hp: u32) -> Self {
+            Monster { _hp: hp }
+        }
+    }
+
+    impl Default for Monster {
+        fn default() -> Self {
+            Monster { _hp: 1 }
+        }
+    }
+
+    impl Drop for Monster {
+        fn drop(&mut self) {
+            println!("I'm dying !");
+        }
+    }
+
+    #[test]
+    fn creation_with_right_capacity() {
+        unsafe {
+            //create a StackAllocator with the specified size.
+            let alloc = StackAllocator::with_capacity(200, 200);
+            let start_chunk = alloc.storage_as_ptr();
+            let first_unused_mem_addr = start_chunk.offset(alloc.marker() as isize);
+
+            assert_eq!(start_chunk, first_unused_mem_addr);
+        }
+    }
+
+    #[test]
+    fn allocation_test() {
+        //We allocate 200 bytes of memory.
+        let alloc = StackAllocator::with_capacity(200, 200);
+
+        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
+
+        unsafe {
+            let start_alloc = alloc.storage_as_ptr();
+            let top_stack_index = alloc.marker();
+            let top_stack = start_alloc.offset(top_stack_index as isize);
+            assert_ne!(start_alloc, top_stack);
+        }
+    }
+
+    //Use 'cargo test -- --nocapture' to see the monsters' println!s
+    #[test]
+    fn test_reset() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
+
+        let top_stack_index = alloc.marker();
+        let start_alloc = alloc.storage_as_ptr();
+        let mut current_top_stack_index = alloc.marker();
+
+        unsafe {
+            let top_stack = start_alloc.offset(top_stack_index as isize);
+            let current_top_stack = start_alloc.offset(current_top_stack_index as isize);
+            assert_eq!(current_top_stack, top_stack);
+        }
+
+        let _another_monster = alloc.alloc(|| Monster::default()).unwrap();
+
+        current_top_stack_index = alloc.marker();
+
+        unsafe {
             let top_stack = start_alloc.offset(top_stack_index as isize);
             let current_top_stack = start_alloc.offset(current_top_stack_index as isize);
             assert_ne!(current_top_stack, top_stack);
         }
 
-        alloc.reset_to_marker(top_stack_index);
+        alloc.reset_to_marker(top_stack_index).unwrap();
 
         //another_monster prints "i'm dying". The drop function is called !
 
@@ -1189,4 +2446,633 @@ mod stack_allocator_test {
             assert_eq!(current_top_stack, start_alloc);
         }
     }
+
+    #[test]
+    fn out_of_memory_error() {
+        let alloc = StackAllocator::with_capacity(200, 10);
+        let _my_i32 = alloc.alloc(|| 8 as i32).unwrap();
+        let fill = alloc.marker_copy();
+
+        let error = alloc.alloc(|| [0 as u64; 4]).unwrap_err();
+        assert_eq!(error, AllocationError::OutOfMemoryError {
+            requested_size: 32,
+            alignment: 8,
+            storage: StorageKind::Copy,
+            fill,
+            capacity: 10,
+        });
+        assert_eq!(
+            format!("{}", error),
+            format!("Out of memory error: cannot allocate 32 bytes aligned on 8 bytes in the copyable data storage ({} / 10 bytes used)", fill)
+        );
+
+        //The failed allocation didn't move the top of the stack.
+        assert_eq!(alloc.marker_copy(), fill);
+
+        match alloc.alloc(|| (Monster::default(), [0 as u64; 32])) {
+            Err(AllocationError::OutOfMemoryError { storage: StorageKind::Drop, capacity: 200, .. }) => {}
+            _ => panic!("The droppable data storage should be out of memory."),
+        }
+    }
+
+    #[test]
+    fn capacity_overflow() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+        let _my_monster = alloc.alloc(|| Monster::default()).unwrap();
+        let _my_i32 = alloc.alloc(|| 8 as i32).unwrap();
+        let fill = alloc.marker();
+        let fill_copy = alloc.marker_copy();
+        let highest_alignment = 1 << (mem::size_of::<usize>() * 8 - 1);
+
+        //Huge sizes.
+        for &(n_bytes, align) in [(usize::MAX, 1), (usize::MAX - 8, 16), (usize::MAX - 15, 16)].iter() {
+            assert_eq!(alloc.alloc_non_copy_inner(n_bytes, align), Err(AllocationError::CapacityOverflowError {
+                requested_size: n_bytes,
+                alignment: align,
+                storage: StorageKind::Drop,
+            }));
+            assert_eq!(alloc.alloc_copy_inner(n_bytes, align), Err(AllocationError::CapacityOverflowError {
+                requested_size: n_bytes,
+                alignment: align,
+                storage: StorageKind::Copy,
+            }));
+        }
+
+        //Huge alignments.
+        assert!(match alloc.alloc_non_copy_inner(highest_alignment, highest_alignment) {
+            Err(AllocationError::CapacityOverflowError { .. }) => true,
+            _ => false,
+        });
+        assert!(match alloc.alloc_copy_inner(highest_alignment, highest_alignment) {
+            Err(AllocationError::CapacityOverflowError { .. }) => true,
+            _ => false,
+        });
+        //Those ones can be represented, but can't fit in memory.
+        assert!(match alloc.alloc_non_copy_inner(8, highest_alignment) {
+            Err(AllocationError::OutOfMemoryError { .. }) => true,
+            _ => false,
+        });
+        assert!(match alloc.alloc_copy_inner(8, highest_alignment) {
+            Err(AllocationError::OutOfMemoryError { .. }) => true,
+            _ => false,
+        });
+
+        //Nothing has been allocated.
+        assert_eq!(alloc.marker(), fill);
+        assert_eq!(alloc.marker_copy(), fill_copy);
+    }
+
+    #[test]
+    fn invalid_marker() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+        let _my_monster = alloc.alloc(|| Monster::default()).unwrap();
+        let _my_i32 = alloc.alloc(|| 8 as i32).unwrap();
+        let fill = alloc.marker();
+        let fill_copy = alloc.marker_copy();
+
+        assert_eq!(alloc.reset_to_marker(fill + 8), Err(AllocationError::InvalidMarkerError {
+            marker: fill + 8,
+            fill,
+            storage: StorageKind::Drop,
+        }));
+        //A header can't lie at this location.
+        assert!(alloc.reset_to_marker(1).is_err());
+        assert_eq!(alloc.reset_to_marker_copy(fill_copy + 1), Err(AllocationError::InvalidMarkerError {
+            marker: fill_copy + 1,
+            fill: fill_copy,
+            storage: StorageKind::Copy,
+        }));
+
+        //Nothing has been reset.
+        assert_eq!(alloc.marker(), fill);
+        assert_eq!(alloc.marker_copy(), fill_copy);
+
+        assert!(alloc.reset_to_marker(0).is_ok());
+        assert!(alloc.reset_to_marker_copy(0).is_ok());
+        assert_eq!(alloc.marker(), 0);
+        assert_eq!(alloc.marker_copy(), 0);
+    }
+
+    #[test]
+    fn marker_inside_an_object() {
+        let alloc = StackAllocator::with_capacity(500, 200);
+        let names = alloc.alloc(|| [String::from("first"), String::from("second"), String::from("third")]).unwrap();
+        let fill = alloc.marker();
+
+        //A marker taken inside the array is aligned like a header, but no header lies there.
+        let info = alloc.allocations().next().unwrap();
+        let inside = info.offset + mem::size_of::<String>();
+        assert_eq!(inside % mem::align_of::<utils::AllocationHeader>(), 0);
+        assert_eq!(alloc.reset_to_marker(inside), Err(AllocationError::InvalidMarkerError {
+            marker: inside,
+            fill,
+            storage: StorageKind::Drop,
+        }));
+
+        //Nothing has been dropped.
+        assert_eq!(alloc.marker(), fill);
+        assert_eq!(names[2], "third");
+        assert!(alloc.reset_to_marker(info.header).is_ok());
+        assert_eq!(alloc.marker(), 0);
+    }
+
+    #[test]
+    fn type_name_in_header() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
+
+        unsafe {
+            //The first header lies at the bottom of the memory storage.
+            let header = &*(alloc.storage_as_ptr() as *const utils::AllocationHeader);
+
+            if cfg!(debug_assertions) {
+                assert!(header.type_name().unwrap().ends_with("Monster"));
+            } else {
+                assert!(header.type_name().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn walk_allocations() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+        assert_eq!(alloc.allocations().count(), 0);
+
+        let my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
+        let my_string = alloc.alloc(|| String::from("hello")).unwrap();
+        //Copyable data doesn't live in the same memory storage.
+        let _my_i32 = alloc.alloc(|| 8 as i32).unwrap();
+
+        let allocations: Vec<_> = alloc.allocations().collect();
+        assert_eq!(allocations.len(), 2);
+
+        let start_alloc = alloc.storage_as_ptr() as usize;
+        assert_eq!(allocations[0].header, 0);
+        assert_eq!(allocations[0].offset, my_monster as *const Monster as usize - start_alloc);
+        assert_eq!(allocations[0].size, mem::size_of::<Monster>());
+        assert_eq!(allocations[0].alignment, mem::align_of::<Monster>());
+        assert!(allocations[0].is_initialized);
+
+        assert_eq!(allocations[1].header, allocations[0].end);
+        assert_eq!(allocations[1].offset, my_string as *const String as usize - start_alloc);
+        assert_eq!(allocations[1].size, mem::size_of::<String>());
+        assert_eq!(allocations[1].end, alloc.marker());
+
+        if cfg!(debug_assertions) {
+            assert!(allocations[1].type_name.unwrap().ends_with("String"));
+        }
+    }
+
+    #[test]
+    fn dump_memory_map() {
+        let alloc = StackAllocator::with_capacity(200, 100);
+        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
+        let _my_i32 = alloc.alloc(|| 8 as i32).unwrap();
+
+        let text = alloc.dump(DumpFormat::Text);
+        assert!(text.contains(&format!("drop storage: {} / 200 bytes used", alloc.marker())));
+        assert!(text.contains(&format!("copy storage: {} / 100 bytes used", alloc.marker_copy())));
+        assert!(text.contains("header"));
+        assert!(text.contains("object"));
+        assert!(text.contains("free"));
+
+        let json = alloc.dump(DumpFormat::Json);
+        assert!(json.contains("{\"name\":\"drop\",\"capacity\":200"));
+        assert!(json.contains("\"kind\":\"object\",\"start\":"));
+        assert!(json.contains(&format!("{{\"kind\":\"free\",\"start\":{},\"end\":100}}", alloc.marker_copy())));
+        if cfg!(debug_assertions) {
+            assert!(json.contains("Monster\"}"));
+        } else {
+            assert!(json.contains("\"type_name\":null"));
+        }
+
+        let svg = alloc.dump(DumpFormat::Svg);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<line").count(), 2);
+    }
+
+
+    #[test]
+    fn rollback_on_panic() {
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct DropCounter<'a>(&'a Cell<usize>);
+
+        impl<'a> Drop for DropCounter<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let alloc = StackAllocator::with_capacity(200, 200);
+        let _first = alloc.alloc(|| DropCounter(&drops)).unwrap();
+        let marker = alloc.marker();
+        let marker_copy = alloc.marker_copy();
+
+        //The memory of a panicking initializer is given back.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            alloc.alloc(|| -> DropCounter { panic!("initialization failed") }).unwrap();
+        }));
+        assert!(result.is_err());
+        assert_eq!(alloc.marker(), marker);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            alloc.alloc_mut_unchecked(|| -> u64 { panic!("initialization failed") });
+        }));
+        assert!(result.is_err());
+        assert_eq!(alloc.marker_copy(), marker_copy);
+
+        //If the initializer allocated before panicking, the allocation is kept but the object is never dropped.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            alloc.alloc(|| -> DropCounter {
+                let _nested = alloc.alloc(|| DropCounter(&drops)).unwrap();
+                panic!("initialization failed")
+            }).unwrap();
+        }));
+        assert!(result.is_err());
+        assert!(alloc.marker() > marker);
+        assert_eq!(alloc.allocations().filter(|info| !info.is_initialized).count(), 1);
+
+        assert_eq!(drops.get(), 0);
+        alloc.reset();
+        assert_eq!(drops.get(), 2);
+    }
+
+
+    #[test]
+    fn fallible_initializer() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+        let _my_monster = alloc.alloc(|| Monster::default()).unwrap();
+        let marker = alloc.marker();
+        let marker_copy = alloc.marker_copy();
+
+        let my_monster = alloc.try_alloc_with(|| Ok::<_, ()>(Monster::new(2))).unwrap();
+        assert_eq!(my_monster._hp, 2);
+        alloc.reset_to_marker(marker).unwrap();
+
+        //The memory reserved for the data is given back when the initializer fails.
+        let result = alloc.try_alloc_with(|| Err::<Monster, _>("invalid monster"));
+        assert_eq!(result.err(), Some(TryAllocError::InitializerError("invalid monster")));
+        assert_eq!(alloc.marker(), marker);
+        assert_eq!(alloc.allocations().count(), 1);
+
+        let result = alloc.try_alloc_mut_with(|| Err::<u64, _>(26));
+        assert_eq!(result.err(), Some(TryAllocError::InitializerError(26)));
+        assert_eq!(alloc.marker_copy(), marker_copy);
+
+        //Allocation errors are reported as such.
+        let result = alloc.try_alloc_with(|| Ok::<_, ()>([0 as u8; 300]));
+        match result {
+            Err(TryAllocError::AllocationError(AllocationError::OutOfMemoryError { .. })) => {},
+            _ => panic!("The allocation should have failed."),
+        }
+    }
+
+
+    #[test]
+    fn in_place_allocation() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let alloc = StackAllocator::with_capacity(200, 5000);
+
+        //Uninitialized copyable data.
+        let big_array = alloc.alloc_uninit::<[u64; 512]>().unwrap();
+        assert_eq!(big_array.as_ptr() as usize % mem::align_of::<[u64; 512]>(), 0);
+        assert!(alloc.marker_copy() >= mem::size_of::<[u64; 512]>());
+
+        //Droppable data is only marked as initialized once the closure confirmed the initialization.
+        let my_monster = alloc
+            .alloc_with_ptr(|slot: InPlace<Monster>| -> Result<_, ()> {
+                assert!(!alloc.allocations().next().unwrap().is_initialized);
+                Ok(slot.write(Monster::new(3)))
+            })
+            .unwrap();
+        assert_eq!(my_monster._hp, 3);
+        assert!(alloc.allocations().next().unwrap().is_initialized);
+
+        //A closure declining the initialization gives the memory back.
+        let marker = alloc.marker();
+        match alloc.alloc_with_ptr(|_: InPlace<Monster>| Err("declined")) {
+            Err(TryAllocError::InitializerError("declined")) => {}
+            _ => panic!("The initialization should have been declined."),
+        }
+        assert_eq!(alloc.marker(), marker);
+
+        //A panicking closure gives the memory back.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            alloc
+                .alloc_with_ptr(|_: InPlace<Monster>| -> Result<_, ()> { panic!("initialization failed") })
+                .unwrap();
+        }));
+        assert!(result.is_err());
+        assert_eq!(alloc.marker(), marker);
+        assert_eq!(alloc.allocations().count(), 1);
+    }
+
+
+    #[test]
+    fn reverse_destruction_order() {
+        use std::cell::RefCell;
+
+        struct Recorder<'a> {
+            id: u32,
+            drops: &'a RefCell<Vec<u32>>,
+        }
+
+        impl<'a> Drop for Recorder<'a> {
+            fn drop(&mut self) {
+                self.drops.borrow_mut().push(self.id);
+            }
+        }
+
+        let drops = RefCell::new(Vec::new());
+        let alloc = StackAllocator::with_capacity(1000, 100);
+        for id in 0..3 {
+            alloc.alloc(|| Recorder { id, drops: &drops }).unwrap();
+        }
+        let marker = alloc.marker();
+        for id in 3..6 {
+            //Objects of different sizes and alignments.
+            alloc.alloc(|| (Recorder { id, drops: &drops }, 0 as u8)).unwrap();
+            alloc.alloc(|| vec![id]).unwrap();
+        }
+
+        alloc.reset_to_marker(marker).unwrap();
+        assert_eq!(*drops.borrow(), vec![5, 4, 3]);
+
+        alloc.reset();
+        assert_eq!(*drops.borrow(), vec![5, 4, 3, 2, 1, 0]);
+
+        //The footers are shown in the memory map.
+        alloc.alloc(|| Recorder { id: 6, drops: &drops }).unwrap();
+        assert!(alloc.dump(DumpFormat::Text).contains("footer"));
+    }
+
+
+    #[test]
+    fn panicking_destructor() {
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct Fragile<'a> {
+            panics: bool,
+            drops: &'a Cell<usize>,
+        }
+
+        impl<'a> Drop for Fragile<'a> {
+            fn drop(&mut self) {
+                self.drops.set(self.drops.get() + 1);
+                if self.panics {
+                    panic!("destructor failed");
+                }
+            }
+        }
+
+        let drops = Cell::new(0);
+        let alloc = StackAllocator::with_capacity(1000, 100);
+        alloc.alloc(|| Fragile { panics: false, drops: &drops }).unwrap();
+        let marker = alloc.marker();
+        for &panics in [false, true, false, true].iter() {
+            alloc.alloc(|| Fragile { panics, drops: &drops }).unwrap();
+        }
+
+        //Every object above the marker is dropped, and the first panic is resumed.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| alloc.reset_to_marker(marker)));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 4);
+        assert_eq!(alloc.marker(), marker);
+        assert_eq!(alloc.allocations().count(), 1);
+
+        alloc.alloc(|| Fragile { panics: true, drops: &drops }).unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| alloc.reset()));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 6);
+        assert_eq!(alloc.marker(), 0);
+
+        //The allocator can still be used, and nothing is dropped twice.
+        alloc.alloc(|| Fragile { panics: false, drops: &drops }).unwrap();
+        alloc.reset();
+        assert_eq!(drops.get(), 7);
+
+        //Dropping the allocator resumes the panic of a destructor.
+        alloc.alloc(|| Fragile { panics: true, drops: &drops }).unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(move || drop(alloc)));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 8);
+    }
+
+
+    #[test]
+    fn reentrant_destructor() {
+        use std::cell::RefCell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct Reentrant<'a> {
+            alloc: &'a StackAllocator,
+            results: &'a RefCell<Vec<AllocationResult<()>>>,
+        }
+
+        impl<'a> Drop for Reentrant<'a> {
+            fn drop(&mut self) {
+                let mut results = self.results.borrow_mut();
+                results.push(self.alloc.alloc(|| Monster::default()).map(|_| ()));
+                results.push(self.alloc.reset_to_marker(0));
+                results.push(self.alloc.alloc(|| 26 as u64).map(|_| ()));
+            }
+        }
+
+        struct Resetter<'a> {
+            alloc: &'a StackAllocator,
+        }
+
+        impl<'a> Drop for Resetter<'a> {
+            fn drop(&mut self) {
+                self.alloc.reset();
+            }
+        }
+
+        let results = RefCell::new(Vec::new());
+        let alloc = StackAllocator::with_capacity(1000, 100);
+        alloc.alloc(|| Monster::default()).unwrap();
+        alloc.alloc(|| Reentrant { alloc: &alloc, results: &results }).unwrap();
+        alloc.reset();
+
+        //The storage holding droppable data cannot be used, the other one can.
+        let reset_in_progress = Err(AllocationError::ResetInProgressError { storage: StorageKind::Drop });
+        assert_eq!(*results.borrow(), vec![reset_in_progress, reset_in_progress, Ok(())]);
+        assert_eq!(alloc.marker(), 0);
+        assert!(alloc.marker_copy() > 0);
+
+        //A reset from a destructor panics, the allocator stays consistent.
+        alloc.alloc(|| Resetter { alloc: &alloc }).unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| alloc.reset()));
+        assert!(result.is_err());
+        assert_eq!(alloc.marker(), 0);
+
+        alloc.alloc(|| Monster::default()).unwrap();
+        assert_eq!(alloc.allocations().count(), 1);
+    }
+
+    #[test]
+    fn child_allocator() {
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct Counted<'a> {
+            drops: &'a Cell<usize>,
+        }
+
+        impl<'a> Drop for Counted<'a> {
+            fn drop(&mut self) {
+                self.drops.set(self.drops.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let parent = StackAllocator::with_capacity(1000, 1000);
+        let parent_string = parent.alloc(|| String::from("parent")).unwrap();
+        let parent_marker_copy = parent.marker_copy();
+
+        {
+            let child = parent.split_off(300, 100).unwrap();
+            assert_eq!(child.capacity(), 300);
+            assert_eq!(child.capacity_copy(), 100);
+            assert_eq!(child.storage_as_ptr() as usize % CHILD_ALIGNMENT, 0);
+
+            child.alloc(|| Counted { drops: &drops }).unwrap();
+            child.alloc(|| 26 as u64).unwrap();
+
+            //Resetting the child never touches the data of the parent.
+            child.reset();
+            child.reset_copy();
+            assert_eq!(drops.get(), 1);
+            assert_eq!(parent_string, "parent");
+
+            //The parent can allocate above the lent memory, and be reset down to it.
+            let child_end = parent.marker_copy();
+            parent.alloc(|| 26 as u64).unwrap();
+            parent.reset_to_marker_copy(child_end).unwrap();
+
+            //The parent cannot be reset below the lent memory.
+            assert_eq!(
+                parent.reset_to_marker_copy(parent_marker_copy),
+                Err(AllocationError::ChildInUseError { marker: parent_marker_copy, child_end })
+            );
+            let result = panic::catch_unwind(AssertUnwindSafe(|| parent.reset_copy()));
+            assert!(result.is_err());
+
+            //The objects of the child are dropped with it.
+            child.alloc(|| Counted { drops: &drops }).unwrap();
+        }
+        assert_eq!(drops.get(), 2);
+
+        //The memory of the dropped child can be given back.
+        parent.reset_to_marker_copy(parent_marker_copy).unwrap();
+        parent.reset_copy();
+
+        //A child which doesn't fit leaves the parent untouched.
+        assert!(parent.split_off(500, 600).is_err());
+        assert_eq!(parent.marker_copy(), 0);
+    }
+
+
+    #[test]
+    fn sub_allocators_in_scoped_threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+
+        struct Counted<'a> {
+            drops: &'a AtomicUsize,
+        }
+
+        impl<'a> Drop for Counted<'a> {
+            fn drop(&mut self) {
+                self.drops.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = AtomicUsize::new(0);
+        let parent = StackAllocator::with_capacity(100, 10000);
+        assert!(parent.partition(0).unwrap().is_empty());
+
+        let sub_allocators = parent.partition(4).unwrap();
+        assert_eq!(sub_allocators.len(), 4);
+        assert!(parent.reset_to_marker_copy(0).is_err());
+
+        let meshes: Vec<&Vec<u32>> = thread::scope(|scope| {
+            let workers: Vec<_> = sub_allocators
+                .into_iter()
+                .enumerate()
+                .map(|(i, sub_allocator)| {
+                    let drops = &drops;
+                    scope.spawn(move || {
+                        sub_allocator.alloc(|| Counted { drops }).unwrap();
+                        sub_allocator.alloc(|| vec![i as u32; 3]).unwrap()
+                    })
+                })
+                .collect();
+
+            workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+        });
+
+        //The data of the workers is still readable from the parent, nothing has been dropped.
+        for (i, mesh) in meshes.iter().enumerate() {
+            assert_eq!(*mesh, &vec![i as u32; 3]);
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+
+        //The objects are dropped when the memory is given back.
+        parent.reset_to_marker_copy(0).unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+
+        //The remaining memory is too small to be shared.
+        parent.alloc(|| [0 as u8; 9990]).unwrap();
+        let marker_copy = parent.marker_copy();
+        assert!(parent.partition(4).is_err());
+        assert_eq!(parent.marker_copy(), marker_copy);
+        parent.reset_copy();
+
+        //The parent drops the data left by the sub-allocators when it is dropped.
+        {
+            let parent = StackAllocator::with_capacity(100, 1000);
+            for sub_allocator in parent.partition(2).unwrap() {
+                sub_allocator.alloc(|| Counted { drops: &drops }).unwrap();
+            }
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 6);
+    }
+
+
+    #[cfg(feature = "allocation_tracking")]
+    #[test]
+    fn call_site_report() {
+        let alloc = StackAllocator::with_capacity(200, 200);
+
+        let _my_monster = alloc.alloc(|| Monster::new(1)).unwrap();
+        let marker = alloc.marker();
+        for _ in 0..2 {
+            let _another_monster = alloc.alloc(|| Monster::default()).unwrap();
+        }
+        let _my_i32 = alloc.alloc(|| 8 as i32).unwrap();
+
+        let report = alloc.report();
+        assert_eq!(report.call_sites().len(), 3);
+        assert_eq!(report.total_count(), 4);
+        assert_eq!(report.total_bytes(), alloc.marker() + alloc.marker_copy());
+
+        //The loop asked for the most memory.
+        assert_eq!(report.call_sites()[0].count, 2);
+        assert_eq!(report.call_sites()[0].location.file(), file!());
+
+        //Rolled back allocations are forgotten.
+        alloc.reset_to_marker(marker).unwrap();
+        assert_eq!(alloc.report().total_count(), 2);
+        alloc.reset_copy();
+        assert_eq!(alloc.report().total_count(), 1);
+        alloc.reset();
+        assert_eq!(alloc.report().total_count(), 0);
+    }
 }
@@ -0,0 +1,624 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::ptr;
+use std::mem;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use allocation_error::{AllocationError, AllocationResult, StorageKind};
+use utils;
+use memory_chunk::MemoryChunk;
+use std::intrinsics::needs_drop;
+
+/// A stack-based allocator which can be shared between threads.
+///
+/// It works like a `StackAllocator`, with two memory storages: one for data implementing the `Drop` trait,
+/// the other for data implementing the `Copy` trait. Its allocation functions take a shared reference and can be
+/// called from several threads at the same time.
+///
+/// # Details
+///
+/// ## Allocation
+/// The first unused byte of each memory storage is an atomic integer. An allocation computes the location of its data
+/// from the current top of the stack, and moves the top of the stack with a compare-and-swap. If another thread
+/// allocated in the meantime, the computation is done again from the new top of the stack.
+///
+/// The header of an object implementing the `Drop` trait is written before the object is initialized. Once the object
+/// is initialized, the header is atomically updated to indicate that the object is there.
+///
+/// The allocated data must implement the `Send` trait: it is dropped by the thread resetting the allocator.
+///
+/// ## Roll-back
+/// Resetting a memory storage requires a mutable reference to the allocator: no thread can allocate
+/// while the objects are being dropped. The objects are dropped in the reverse order of their allocation.
+///
+/// The allocations are not recorded by the `allocation_tracking` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use maskerad_memory_allocators::SyncStackAllocator;
+/// use std::sync::Arc;
+/// use std::thread;
+/// # use std::error::Error;
+/// # fn try_main() -> Result<(), Box<Error>> {
+/// let allocator = Arc::new(SyncStackAllocator::with_capacity(100, 1000));
+///
+/// let workers: Vec<_> = (0..4).map(|i| {
+///     let allocator = allocator.clone();
+///     thread::spawn(move || {
+///         for j in 0..10 {
+///             let my_u64 = allocator.alloc(|| i * 10 + j as u64).unwrap();
+///             assert_eq!(*my_u64, i * 10 + j);
+///         }
+///     })
+/// }).collect();
+///
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+///
+/// // 40 u64, and the padding needed to align them.
+/// assert!(allocator.marker_copy() >= 40 * 8);
+///
+/// let mut allocator = Arc::try_unwrap(allocator).unwrap();
+/// allocator.reset_copy();
+/// assert_eq!(allocator.marker_copy(), 0);
+/// # Ok(())
+/// # }
+/// # fn main() {
+/// #   try_main().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SyncStackAllocator {
+    storage: MemoryChunk,
+    storage_copy: MemoryChunk,
+    /// Index of the first unused byte of the memory storage holding droppable data.
+    fill: AtomicUsize,
+    /// Index of the first unused byte of the memory storage holding copyable data.
+    fill_copy: AtomicUsize,
+}
+
+//The memory chunks are only read through a shared reference, to get their memory storage and their capacity.
+//Their first unused byte is only updated through a mutable reference, when they drop their objects.
+unsafe impl Sync for SyncStackAllocator {}
+
+impl SyncStackAllocator {
+    /// Creates a SyncStackAllocator with the given capacities, in bytes.
+    ///
+    /// The first capacity is for the memory storage holding data implementing the `Drop` trait,
+    /// the second is for the memory storage holding data implementing the `Copy` trait.
+    /// # Example
+    ///
+    /// ```rust
+    /// use maskerad_memory_allocators::SyncStackAllocator;
+    ///
+    /// let allocator = SyncStackAllocator::with_capacity(100, 50);
+    /// assert_eq!(allocator.capacity(), 100);
+    /// assert_eq!(allocator.capacity_copy(), 50);
+    /// ```
+    pub fn with_capacity(capacity: usize, capacity_copy: usize) -> Self {
+        debug!("Creating a sync stack allocator of {} bytes for droppable data and {} bytes for copyable data.", capacity, capacity_copy);
+        SyncStackAllocator {
+            storage: MemoryChunk::new(capacity),
+            storage_copy: MemoryChunk::new(capacity_copy),
+            fill: AtomicUsize::new(0),
+            fill_copy: AtomicUsize::new(0),
+        }
+    }
+
+    /// Allocates data in the allocator's memory, returning a mutable reference to the allocated data.
+    ///
+    /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
+    /// Otherwise, it will be placed in the other memory storage.
+    ///
+    /// If the closure panics, the memory reserved for the data is given back to the allocator if no other thread
+    /// allocated in the same memory storage in the meantime.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    ///
+    /// # Example
+    /// ```
+    /// use maskerad_memory_allocators::SyncStackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = SyncStackAllocator::with_capacity(100, 100);
+    ///
+    /// let my_string = allocator.alloc_mut(|| String::from("sync"))?;
+    /// my_string.push_str(" stack");
+    ///
+    /// assert_eq!(my_string, "sync stack");
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn alloc_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in the sync stack allocator, returning a mutable reference.");
+        unsafe {
+            if needs_drop::<T>() {
+                trace!("The data to allocate is droppable.");
+                self.alloc_non_copy_mut(op)
+            } else {
+                trace!("The data to allocate is copyable.");
+                self.alloc_copy_mut(op)
+            }
+        }
+    }
+
+    /// Allocates data in the allocator's memory, returning an immutable reference to the allocated data.
+    ///
+    /// If the allocated data implements `Drop`, it will be placed in the memory storage storing data implementing the `Drop` trait.
+    /// Otherwise, it will be placed in the other memory storage.
+    ///
+    /// If the closure panics, the memory reserved for the data is given back to the allocator if no other thread
+    /// allocated in the same memory storage in the meantime.
+    ///
+    /// # Error
+    /// This function will return an error if the allocation exceeds the maximum storage capacity of the allocator.
+    ///
+    /// # Example
+    /// ```
+    /// use maskerad_memory_allocators::SyncStackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let allocator = SyncStackAllocator::with_capacity(100, 100);
+    ///
+    /// let my_i32 = allocator.alloc(|| 26 as i32)?;
+    /// assert_eq!(my_i32, &26);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn alloc<T, F>(&self, op: F) -> AllocationResult<&T>
+    where
+        T: Send,
+        F: FnOnce() -> T,
+    {
+        debug!("Allocating data in the sync stack allocator, returning an immutable reference.");
+        self.alloc_mut(op).map(|object| &*object)
+    }
+
+    fn alloc_non_copy_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        trace!("Allocating mutable and droppable data in the sync stack allocator.");
+        unsafe {
+            //Get the type description of the type T (get its vtable).
+            trace!("Getting a TypeDescription of the data being allocated.");
+            let type_description = utils::get_type_description::<T>();
+
+            //Reserve the memory locations of the header, the object and the footer.
+            trace!("Reserving the memory locations of the header, the data and the footer.");
+            let (header_start, start, end) =
+                self.reserve_non_copy(mem::size_of::<T>(), mem::align_of::<T>())?;
+
+            let storage_start = self.storage.as_ptr() as *mut u8;
+            let header_ptr = storage_start.offset(header_start as isize) as *mut utils::AllocationHeader;
+            let ptr = storage_start.offset(start as isize) as *mut T;
+
+            //write in our header the type description, along with a bit indicating that the object has *not*
+            //been initialized yet.
+            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to false.");
+            ptr::write(header_ptr, utils::AllocationHeader::new::<T>(type_description));
+
+            //Initialize the object.
+            //If the initializer panics, the guard gives the memory back to the allocator.
+            trace!("Initializing the data.");
+            let guard = RollbackGuard {
+                fill: &self.fill,
+                start: header_start,
+                end,
+            };
+            ptr::write(ptr, op());
+            mem::forget(guard);
+
+            //Publish the object: the type description is the first field of the header, it is updated atomically
+            //once the object has been written.
+            trace!("Packing in the low bit of the TypeDescription the 'is_done' state to true.");
+            (*(header_ptr as *const AtomicUsize)).store(
+                utils::bitpack_type_description_ptr(type_description, true),
+                Ordering::Release,
+            );
+
+            trace!("Returning a mutable reference to the allocated data.");
+            Ok(&mut *ptr)
+        }
+    }
+
+    fn alloc_copy_mut<T, F>(&self, op: F) -> AllocationResult<&mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        trace!("Allocating mutable and copyable data in the sync stack allocator.");
+        unsafe {
+            //Reserve the memory location of the object.
+            trace!("Reserving the memory location of the data.");
+            let (fill, start, end) = self.reserve_copy(mem::size_of::<T>(), mem::align_of::<T>())?;
+            let ptr = (self.storage_copy.as_ptr() as *mut u8).offset(start as isize) as *mut T;
+
+            //Write the data in the memory location.
+            //If the initializer panics, the guard gives the memory back to the allocator.
+            trace!("Initializing the data.");
+            let guard = RollbackGuard {
+                fill: &self.fill_copy,
+                start: fill,
+                end,
+            };
+            ptr::write(ptr, op());
+            mem::forget(guard);
+
+            trace!("Returning a mutable reference to the allocated data.");
+            Ok(&mut *ptr)
+        }
+    }
+
+    /// Moves the top of the stack of the memory storage holding droppable data, returning the indices of the header,
+    /// the object and the first byte after the allocation.
+    fn reserve_non_copy(&self, n_bytes: usize, align: usize) -> AllocationResult<(usize, usize, usize)> {
+        trace!("The droppable data has a size of {} bytes and an alignment of {} bytes.", n_bytes, align);
+        //The error returned if one of the indices below can't be represented.
+        let overflow = AllocationError::CapacityOverflowError {
+            requested_size: n_bytes,
+            alignment: align,
+            storage: StorageKind::Drop,
+        };
+
+        let mut fill = self.fill.load(Ordering::Relaxed);
+        loop {
+            //The header is written at the top of the stack, followed by the object and its footer.
+            let start = fill
+                .checked_add(mem::size_of::<utils::AllocationHeader>())
+                .and_then(|after_header| utils::round_up(after_header, align))
+                .ok_or(overflow)?;
+            let footer_start = start
+                .checked_add(n_bytes)
+                .and_then(|end| utils::round_up(end, mem::align_of::<utils::AllocationFooter>()))
+                .ok_or(overflow)?;
+            let end = footer_start
+                .checked_add(mem::size_of::<utils::AllocationFooter>())
+                .ok_or(overflow)?;
+
+            if end >= self.storage.capacity() {
+                error!("The allocator doesn't have enough remaining memory to store the data !");
+                return Err(AllocationError::OutOfMemoryError {
+                    requested_size: n_bytes,
+                    alignment: align,
+                    storage: StorageKind::Drop,
+                    fill,
+                    capacity: self.storage.capacity(),
+                });
+            }
+
+            //The memory locations are only used by this thread, the ordering of the other memory accesses doesn't matter.
+            match self.fill.compare_exchange_weak(fill, end, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => {
+                    trace!("The allocation lies between the byte {} and the byte {}.", fill, end);
+                    unsafe {
+                        //Write the footer, linking the end of the allocation to its header.
+                        let footer = self.storage.as_ptr().offset(footer_start as isize) as *mut utils::AllocationFooter;
+                        ptr::write(footer, utils::AllocationFooter { header: fill });
+                    }
+                    return Ok((fill, start, end));
+                }
+                Err(current) => {
+                    trace!("Another thread allocated in the memory storage, trying again.");
+                    fill = current;
+                }
+            }
+        }
+    }
+
+    /// Moves the top of the stack of the memory storage holding copyable data, returning the indices of the previous
+    /// top of the stack, the object and the first byte after the allocation.
+    fn reserve_copy(&self, n_bytes: usize, align: usize) -> AllocationResult<(usize, usize, usize)> {
+        trace!("The copyable data has a size of {} bytes and an alignment of {} bytes.", n_bytes, align);
+        //The error returned if one of the indices below can't be represented.
+        let overflow = AllocationError::CapacityOverflowError {
+            requested_size: n_bytes,
+            alignment: align,
+            storage: StorageKind::Copy,
+        };
+
+        let mut fill = self.fill_copy.load(Ordering::Relaxed);
+        loop {
+            let start = utils::round_up(fill, align).ok_or(overflow)?;
+            let end = start.checked_add(n_bytes).ok_or(overflow)?;
+
+            if end >= self.storage_copy.capacity() {
+                error!("The allocator doesn't have enough remaining memory to store the data !");
+                return Err(AllocationError::OutOfMemoryError {
+                    requested_size: n_bytes,
+                    alignment: align,
+                    storage: StorageKind::Copy,
+                    fill,
+                    capacity: self.storage_copy.capacity(),
+                });
+            }
+
+            //The memory location is only used by this thread, the ordering of the other memory accesses doesn't matter.
+            match self.fill_copy.compare_exchange_weak(fill, end, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => {
+                    trace!("The allocation lies between the byte {} and the byte {}.", fill, end);
+                    return Ok((fill, start, end));
+                }
+                Err(current) => {
+                    trace!("Another thread allocated in the memory storage, trying again.");
+                    fill = current;
+                }
+            }
+        }
+    }
+
+    /// Returns the index of the first unused memory address of the memory storage storing data implementing the `Drop` trait.
+    ///
+    /// Other threads may allocate data right after this function returns.
+    pub fn marker(&self) -> usize {
+        debug!("Getting the first unused byte of memory in the memory chunk holding droppable data.");
+        self.fill.load(Ordering::Relaxed)
+    }
+
+    /// Returns the index of the first unused memory address of the memory storage storing data implementing the `Copy` trait.
+    ///
+    /// Other threads may allocate data right after this function returns.
+    pub fn marker_copy(&self) -> usize {
+        debug!("Getting the first unused byte of memory in the memory chunk holding copyable data.");
+        self.fill_copy.load(Ordering::Relaxed)
+    }
+
+    /// Reset the memory storage storing data implementing the `Drop` trait, dropping all the content residing inside it.
+    ///
+    /// The objects are dropped in the reverse order of their allocation, the most recent first.
+    ///
+    /// # Panic
+    /// If a destructor panics, the remaining objects are still dropped and the memory storage is reset.
+    /// The panic of the first panicking destructor is then resumed.
+    ///
+    /// # Example
+    /// ```
+    /// use maskerad_memory_allocators::SyncStackAllocator;
+    /// # use std::error::Error;
+    /// # fn try_main() -> Result<(), Box<Error>> {
+    /// let mut allocator = SyncStackAllocator::with_capacity(100, 100);
+    ///
+    /// allocator.alloc(|| String::from("dropped"))?;
+    /// assert!(allocator.marker() > 0);
+    ///
+    /// allocator.reset();
+    /// assert_eq!(allocator.marker(), 0);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {
+    /// #   try_main().unwrap();
+    /// # }
+    /// ```
+    pub fn reset(&mut self) {
+        debug!("Resetting completely the memory chunk holding droppable data of the sync stack allocator.");
+        let destroyed = self.destroy_to_marker(0);
+        if let Err(payload) = destroyed {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Reset the memory storage storing data implementing the `Copy` trait.
+    pub fn reset_copy(&mut self) {
+        debug!("Resetting completely the memory chunk holding copyable data of the sync stack allocator.");
+        *self.fill_copy.get_mut() = 0;
+    }
+
+    /// Reset partially the memory storage storing data implementing the `Drop` trait, dropping all the content residing between the marker and
+    /// the first unused memory address of the memory storage.
+    ///
+    /// The objects are dropped in the reverse order of their allocation, the most recent first.
+    ///
+    /// # Panic
+    /// If a destructor panics, the remaining objects are still dropped and the memory storage is reset to the marker.
+    /// The panic of the first panicking destructor is then resumed.
+    ///
+    /// # Error
+    /// This function will return an error if the marker lies after the first unused memory address, or is not the location of
+    /// an object's header.
+    pub fn reset_to_marker(&mut self, marker: usize) -> AllocationResult<()> {
+        debug!("Resetting partially the memory chunk holding droppable data of the sync stack allocator to the marker {}.", marker);
+        //A marker must lie below the top of the stack, on the location of a header.
+        //No other thread can allocate: the memory chunk can use its own first unused byte to walk the objects.
+        let fill = *self.fill.get_mut();
+        self.storage.set_fill(fill);
+        if !unsafe { self.storage.is_allocation_boundary(marker) } {
+            error!("The marker {} is not a valid location in the memory chunk !", marker);
+            return Err(AllocationError::InvalidMarkerError {
+                marker,
+                fill,
+                storage: StorageKind::Drop,
+            });
+        }
+
+        let destroyed = self.destroy_to_marker(marker);
+        if let Err(payload) = destroyed {
+            panic::resume_unwind(payload);
+        }
+
+        Ok(())
+    }
+
+    /// Reset partially the memory storage storing data implementing the `Copy` trait.
+    ///
+    /// # Error
+    /// This function will return an error if the marker lies after the first unused memory address.
+    pub fn reset_to_marker_copy(&mut self, marker: usize) -> AllocationResult<()> {
+        debug!("Resetting partially the memory chunk holding copyable data of the sync stack allocator to the marker {}.", marker);
+        let fill = *self.fill_copy.get_mut();
+        if marker > fill {
+            error!("The marker {} is not a valid location in the memory chunk !", marker);
+            return Err(AllocationError::InvalidMarkerError {
+                marker,
+                fill,
+                storage: StorageKind::Copy,
+            });
+        }
+
+        *self.fill_copy.get_mut() = marker;
+        Ok(())
+    }
+
+    /// Returns the maximum capacity the memory storage storing data implementing the `Drop` trait can hold.
+    pub fn capacity(&self) -> usize {
+        debug!("Getting the maximum capacity, in bytes, of the memory chunk holding droppable data.");
+        self.storage.capacity()
+    }
+
+    /// Returns the maximum capacity the memory storage storing data implementing the `Copy` trait can hold.
+    pub fn capacity_copy(&self) -> usize {
+        debug!("Getting the maximum capacity, in bytes, of the memory chunk holding copyable data.");
+        self.storage_copy.capacity()
+    }
+
+    /// Drops the objects lying above the marker and moves the top of the stack to the marker, returning the payload
+    /// of the first panicking destructor.
+    fn destroy_to_marker(&mut self, marker: usize) -> ::std::thread::Result<()> {
+        //No other thread can allocate: the memory chunk can use its own first unused byte to walk the objects.
+        self.storage.set_fill(*self.fill.get_mut());
+        let destroyed = unsafe { self.storage.destroy_to_marker(marker) };
+        self.storage.set_fill(marker);
+        *self.fill.get_mut() = marker;
+        destroyed
+    }
+}
+
+impl Drop for SyncStackAllocator {
+    fn drop(&mut self) {
+        debug!("The SyncStackAllocator is being dropped, all droppable data is being dropped.");
+        let destroyed = self.destroy_to_marker(0);
+        if let Err(payload) = destroyed {
+            //Resuming a panic while the thread is already unwinding would abort the process.
+            if thread::panicking() {
+                error!("A destructor panicked while the sync stack allocator was dropped during a panic, the panic is discarded !");
+            } else {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+/// Gives back the memory of an allocation to the allocator if the initializer of the allocated data panics.
+///
+/// The memory is only given back if no other thread allocated after it. Otherwise, the header of a droppable object
+/// is still marked as not initialized, the object will not be dropped.
+struct RollbackGuard<'a> {
+    fill: &'a AtomicUsize,
+    /// Index of the first byte used by the allocation.
+    start: usize,
+    /// Index of the first byte after the allocation.
+    end: usize,
+}
+
+impl<'a> Drop for RollbackGuard<'a> {
+    fn drop(&mut self) {
+        match self.fill.compare_exchange(self.end, self.start, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => debug!("The initializer panicked, rolling back the allocation to byte {}.", self.start),
+            Err(_) => debug!("The initializer panicked after other allocations, the allocation is kept."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod sync_stack_allocator_test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    struct Counted {
+        drops: Arc<AtomicUsize>,
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn concurrent_allocations() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let alloc = Arc::new(SyncStackAllocator::with_capacity(100_000, 100_000));
+
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let drops = drops.clone();
+                let alloc = alloc.clone();
+                thread::spawn(move || {
+                    let mut values = Vec::new();
+                    for j in 0..100 {
+                        let value = alloc.alloc(|| (i * 1000 + j) as u64).unwrap();
+                        alloc.alloc(|| Counted { drops: drops.clone() }).unwrap();
+                        values.push(value);
+                    }
+                    //No other thread wrote in our memory locations.
+                    for (j, value) in values.iter().enumerate() {
+                        assert_eq!(**value, (i * 1000 + j) as u64);
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let mut alloc = Arc::try_unwrap(alloc).unwrap();
+        alloc.reset();
+        assert_eq!(drops.load(Ordering::SeqCst), 400);
+        assert_eq!(alloc.marker(), 0);
+    }
+
+    #[test]
+    fn reset_to_marker() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut alloc = SyncStackAllocator::with_capacity(1000, 100);
+        alloc.alloc(|| Counted { drops: drops.clone() }).unwrap();
+        let marker = alloc.marker();
+        alloc.alloc(|| Counted { drops: drops.clone() }).unwrap();
+        let my_u32 = alloc.alloc(|| 3 as u32).unwrap();
+        assert_eq!(my_u32, &3);
+
+        assert!(alloc.reset_to_marker(alloc.marker() + 8).is_err());
+        //The marker lies inside the second allocation.
+        assert!(alloc.reset_to_marker(marker + 8).is_err());
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        alloc.reset_to_marker(marker).unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(alloc.marker(), marker);
+
+        alloc.reset_to_marker_copy(0).unwrap();
+        assert_eq!(alloc.marker_copy(), 0);
+
+        drop(alloc);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let alloc = SyncStackAllocator::with_capacity(100, 10);
+        match alloc.alloc(|| [0 as u8; 20]) {
+            Err(AllocationError::OutOfMemoryError { storage: StorageKind::Copy, .. }) => {},
+            _ => panic!("The allocation should have failed."),
+        }
+        assert_eq!(alloc.marker_copy(), 0);
+    }
+}
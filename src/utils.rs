@@ -21,6 +21,54 @@ pub struct TypeDescription {
     pub alignment: usize,
 }
 
+/// The header placed before every object implementing the `Drop` trait in a memory storage.
+///
+/// The TypeDescription is a view over the vtable of the type, we cannot add fields to it. Informations
+/// about the object which are not in the vtable are stored in this header instead.
+#[repr(C)]
+pub struct AllocationHeader {
+    /// The raw pointer to the TypeDescription of the object, with the 'is_done' state packed in its low bit.
+    pub type_description: usize,
+    /// The name of the type of the object. Only recorded in debug builds.
+    #[cfg(debug_assertions)]
+    pub type_name: &'static str,
+}
+
+impl AllocationHeader {
+    /// Creates the header of an object of type T, which has not been initialized yet.
+    #[inline]
+    pub fn new<T>(type_description: *const TypeDescription) -> Self {
+        AllocationHeader {
+            type_description: bitpack_type_description_ptr(type_description, false),
+            #[cfg(debug_assertions)]
+            type_name: ::std::any::type_name::<T>(),
+        }
+    }
+
+    /// Returns the name of the type of the object, if it has been recorded.
+    #[inline]
+    pub fn type_name(&self) -> Option<&'static str> {
+        #[cfg(debug_assertions)]
+        {
+            Some(self.type_name)
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            None
+        }
+    }
+}
+
+/// The footer placed after an object implementing the `Drop` trait.
+///
+/// It holds the index of the header of the object, so the memory chunk can walk its objects from the top of the stack
+/// to its bottom. A footer has the same alignment as a header: the next header lies just after it.
+#[repr(C)]
+pub struct AllocationFooter {
+    /// The index of the header of the object.
+    pub header: usize,
+}
+
 /// Returns a constant raw pointer to a TypeDescription structure, from a given T.
 ///
 /// # implementation details
@@ -127,19 +175,27 @@ pub fn un_bitpack_type_description_ptr(p: usize) -> (*const TypeDescription, boo
 /// **4-byte** aligned data must reside in memory addresses finishing by 0x0, 0x4, 0x8 and 0xC. Our
 /// aligned_address is properly aligned !
 ///
+/// # Overflow
+///
+/// Returns `None` if the aligned memory location cannot be represented by a `usize`.
 #[inline]
-pub fn round_up(base: usize, align: usize) -> usize {
+pub fn round_up(base: usize, align: usize) -> Option<usize> {
     debug!("Getting an aligned memory location, according to the memory location {:x} and an alignment need of {} bytes", base, align);
     //(base.checked_add(align - 1)).unwrap() & !(align - 1)
     //The solution above works, but our solution is easier to understand and faster.
 
     let misalignment = base & (align - 1);
     trace!("Misaligned by: {:x}", misalignment);
+    if misalignment == 0 {
+        return Some(base);
+    }
+
     let adjustment = align - misalignment;
     trace!("Must be aligned by: {:x}", adjustment);
-    trace!("aligned memory location: {:x}", base + adjustment);
+    let aligned = base.checked_add(adjustment);
+    trace!("aligned memory location: {:x?}", aligned);
 
-    base + adjustment
+    aligned
 }
 
 trait AllTypes {
@@ -147,3 +203,28 @@ trait AllTypes {
 }
 
 impl<T: ?Sized> AllTypes for T {}
+
+#[cfg(test)]
+mod utils_test {
+    use super::*;
+    use std::usize;
+
+    #[test]
+    fn round_up_aligns() {
+        assert_eq!(round_up(0x60758912, 4), Some(0x60758914));
+        assert_eq!(round_up(3, 1), Some(3));
+        assert_eq!(round_up(17, 16), Some(32));
+        //An aligned location is left untouched.
+        assert_eq!(round_up(32, 16), Some(32));
+    }
+
+    #[test]
+    fn round_up_overflow() {
+        assert_eq!(round_up(usize::MAX, 1), Some(usize::MAX));
+        assert_eq!(round_up(usize::MAX, 2), None);
+        assert_eq!(round_up(usize::MAX - 3, 8), None);
+        assert_eq!(round_up(1, 1 << (mem::size_of::<usize>() * 8 - 1)), Some(1 << (mem::size_of::<usize>() * 8 - 1)));
+        assert_eq!(round_up(usize::MAX >> 1, 1 << (mem::size_of::<usize>() * 8 - 1)), Some(1 << (mem::size_of::<usize>() * 8 - 1)));
+        assert_eq!(round_up((usize::MAX >> 1) + 2, 1 << (mem::size_of::<usize>() * 8 - 1)), None);
+    }
+}